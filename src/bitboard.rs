@@ -0,0 +1,139 @@
+//! Integer types usable as bitboards.
+//!
+//! A [`State`](crate::State) stores one bitboard per side, with one bit per square, so its
+//! bitboard type must have at least as many bits as the board has squares. Small boards can use
+//! `u32` to halve the size of tables keyed on states, 8x8 and smaller boards `u64`, and larger ones
+//! `u128`.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::State;
+//! assert_eq!(State::<4, 4, u32>::default().perft(4), 2632);
+//! assert_eq!(State::<4, 4, u128>::default().perft(4), 2632);
+//! ```
+
+use bit_iter::BitIter;
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr},
+};
+
+/// An unsigned integer holding one bit per square.
+pub trait Bitboard:
+    Copy
+    + Ord
+    + Hash
+    + Debug
+    + Default
+    + Send
+    + Sync
+    + 'static
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    const BITS: u32;
+    const ZERO: Self;
+    const ONE: Self;
+
+    type Bits: Iterator<Item = (u32, Self)>;
+
+    /// Iterates over the set bits, yielding each one's index and mask.
+    fn bits(self) -> Self::Bits;
+
+    /// Converts from a `u64`, which must fit.
+    fn from_u64(value: u64) -> Self;
+
+    /// Converts from a `u128`, dropping the bits that don't fit.
+    fn from_u128(value: u128) -> Self;
+
+    fn reverse_bits(self) -> Self;
+
+    fn count_ones(self) -> u32;
+
+    /// Folds the bits into a `u64`, for hashing. Bitboards that fit in a `u64` are unchanged.
+    fn fold(self) -> u64;
+}
+
+macro_rules! impl_bitboard {
+    ($($ty:ty),*) => {$(
+        impl Bitboard for $ty {
+            const BITS: u32 = <$ty>::BITS;
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            type Bits = BitIter<$ty>;
+
+            #[inline]
+            fn bits(self) -> Self::Bits {
+                BitIter::from(self)
+            }
+
+            #[inline]
+            fn from_u64(value: u64) -> Self {
+                value as $ty
+            }
+
+            #[inline]
+            fn from_u128(value: u128) -> Self {
+                value as $ty
+            }
+
+            #[inline]
+            fn reverse_bits(self) -> Self {
+                <$ty>::reverse_bits(self)
+            }
+
+            #[inline]
+            fn count_ones(self) -> u32 {
+                <$ty>::count_ones(self)
+            }
+
+            #[inline]
+            fn fold(self) -> u64 {
+                let high = (self as u128 >> 64) as u64;
+                self as u64 ^ high.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            }
+        }
+    )*};
+}
+
+impl_bitboard!(u32, u64, u128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<B: Bitboard>() {
+        let x = B::from_u64(0b1011_0000_0001);
+        let bits: Vec<_> = x.bits().collect();
+        assert_eq!(
+            bits,
+            [
+                (0, B::ONE),
+                (8, B::ONE << 8),
+                (9, B::ONE << 9),
+                (11, B::ONE << 11)
+            ]
+        );
+        assert_eq!(x.count_ones(), 4);
+        let reversed = [1, 9, 10, 12]
+            .into_iter()
+            .fold(B::ZERO, |acc, i| acc | B::ONE << B::BITS - i);
+        assert_eq!(x.reverse_bits(), reversed);
+        assert_eq!(B::ZERO.bits().next(), None);
+        assert_eq!(B::from_u64(0xdead_beef).fold(), 0xdead_beef);
+    }
+
+    #[test]
+    fn implementations() {
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+}
@@ -0,0 +1,137 @@
+//! Static evaluation of positions, for the leaves of a search.
+//!
+//! An [`Evaluator`] scores a position for the side to move. [`Weights`] is the default one: a
+//! linear combination of features computed with bitboard operations, each counted for the side to
+//! move minus the opponent. The weights are plain fields, so that they can be tuned.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{eval::{Evaluator, Weights}, State};
+//! let weights = Weights::default();
+//! assert_eq!(weights.evaluate(State::<6, 6>::default()), 0);
+//! let state: State<4, 5> = "WWW1/4/4/4/B3 w".parse().unwrap();
+//! assert!(weights.evaluate(state) > 0);
+//! ```
+
+use crate::{Bitboard, State};
+
+/// Scores positions for the side to move.
+pub trait Evaluator<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> {
+    /// Scores `state`, which hasn't been lost, for the side to move. Scores should stay far from
+    /// [`WIN`](crate::search::WIN), which a search clamps them to.
+    fn evaluate(&self, state: State<WIDTH, HEIGHT, B>) -> i32;
+}
+
+/// The weights of the default evaluation, per piece with the given feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Weights {
+    pub material: i32,
+    /// Per row a piece has advanced from its home row.
+    pub advancement: i32,
+    /// For a piece defended by another, which can recapture if it is taken.
+    pub defended: i32,
+    /// Subtracted for a piece attacked and not defended.
+    pub hanging: i32,
+    /// For a piece left on the home row, guarding the squares the opponent must reach.
+    pub home_row: i32,
+    /// For a piece at most two moves from the last row that isn't attacked.
+    pub threat: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            material: 100,
+            advancement: 10,
+            defended: 5,
+            hanging: 30,
+            home_row: 10,
+            threat: 40,
+        }
+    }
+}
+
+impl Weights {
+    /// The score of the side to move in `state`, without the opponent's.
+    fn side<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+        &self,
+        state: State<WIDTH, HEIGHT, B>,
+    ) -> i32 {
+        let home_row = B::from_u64(State::<WIDTH, HEIGHT, B>::ROW_MASK);
+        let near_last_row = (home_row | home_row << WIDTH) << (HEIGHT - 3) * WIDTH;
+        let own = state.me;
+        let count = |board: B| board.count_ones() as i32;
+        let advancement = own.bits().map(|(i, _)| (i / WIDTH) as i32).sum::<i32>();
+
+        self.material * count(own)
+            + self.advancement * advancement
+            + self.defended * count(own & state.attacks_mine())
+            - self.hanging * count(state.hanging())
+            + self.home_row * count(own & home_row)
+            + self.threat * count(own & near_last_row & !state.attacks_theirs())
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Evaluator<WIDTH, HEIGHT, B> for Weights {
+    fn evaluate(&self, state: State<WIDTH, HEIGHT, B>) -> i32 {
+        self.side(state) - self.side(state.flipped())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Weights counting only the feature set by `set`.
+    fn only(set: impl FnOnce(&mut Weights)) -> Weights {
+        let mut weights = Weights {
+            material: 0,
+            advancement: 0,
+            defended: 0,
+            hanging: 0,
+            home_row: 0,
+            threat: 0,
+        };
+        set(&mut weights);
+        weights
+    }
+
+    #[test]
+    fn features() {
+        // White on a1, b1, c1, a3 and b3; Black on c4, d4, a5 and d5.
+        let state: State<4, 5> = "WWW1/4/WW2/2BB/B2B w".parse().unwrap();
+        let score = |weights: Weights| weights.evaluate(state);
+        assert_eq!(score(only(|w| w.material = 1)), 5 - 4);
+        assert_eq!(score(only(|w| w.advancement = 1)), 4 - 2);
+        // d5 defends c4, which b3 attacks; c4 attacks b3, which is undefended.
+        assert_eq!(score(only(|w| w.defended = 1)), -1);
+        assert_eq!(score(only(|w| w.hanging = 1)), -1);
+        assert_eq!(score(only(|w| w.home_row = 1)), 3 - 2);
+        // a3 can reach the last row in two moves; b3 could too, but is attacked.
+        assert_eq!(score(only(|w| w.threat = 1)), 1);
+    }
+
+    #[test]
+    fn symmetric() {
+        let weights = Weights::default();
+        let state = State::<5, 6>::default();
+        assert_eq!(weights.evaluate(state), 0);
+        let mut state = state;
+        for _ in 0..6 {
+            state = state.children().last().unwrap();
+            assert_eq!(weights.evaluate(state), weights.evaluate(state.mirror()));
+        }
+    }
+
+    #[test]
+    fn edges_dont_wrap() {
+        let hanging = only(|w| w.hanging = 1);
+        // d1 and a3 would attack each other if diagonals wrapped around the board.
+        let state: State<4, 5> = "3W/4/B3/4/4 w".parse().unwrap();
+        assert_eq!(hanging.evaluate(state), 0);
+        // b2 and c3 attack each other, and only b2 is defended.
+        let state: State<4, 5> = "W3/1W2/2B1/4/4 w".parse().unwrap();
+        assert_eq!(hanging.evaluate(state), 1);
+    }
+}
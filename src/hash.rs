@@ -0,0 +1,125 @@
+//! Hashing of positions for table lookups.
+//!
+//! [`State::hash64`] mixes both sides' bitboards into a well-distributed 64-bit key, whose low and
+//! high bits are both usable as table indices. The `Hash` implementation of `State` feeds it to
+//! the hasher, and [`StateHasher`] passes it through unchanged, so that hash maps keyed on states
+//! skip rehashing.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::State;
+//! let state = State::<4, 4>::default();
+//! let child = state.children().next().unwrap();
+//! assert_ne!(state.hash64(), child.hash64());
+//! ```
+
+use crate::{Bitboard, State};
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+
+/// The finalizer of MurmurHash3, a bijection of `u64` with good avalanche behaviour.
+#[inline]
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    x ^ x >> 33
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> State<WIDTH, HEIGHT, B> {
+    /// A 64-bit hash of the position, for transposition tables.
+    #[inline]
+    pub fn hash64(self) -> u64 {
+        mix(mix(self.me.fold()) ^ self.them.fold().rotate_left(32))
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Hash for State<WIDTH, HEIGHT, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash64());
+    }
+}
+
+/// A hasher that passes [`State::hash64`] through, for hash maps keyed on states.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateHasher(u64);
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = mix(self.0 ^ byte as u64);
+        }
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = mix(self.0) ^ hash;
+    }
+}
+
+/// The number of `T` entries in a table of about `megabytes` MiB: a power of two, rounded down so
+/// as not to overshoot the budget, but at least one.
+pub(crate) fn table_len<T>(megabytes: usize) -> usize {
+    let capacity = (megabytes << 20) / std::mem::size_of::<T>();
+    1 << capacity.max(1).ilog2()
+}
+
+/// Builds [`StateHasher`]s, e.g. `HashMap<State<W, H>, V, BuildStateHasher>`.
+pub type BuildStateHasher = BuildHasherDefault<StateHasher>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::verify;
+    use std::collections::HashSet;
+
+    #[test]
+    fn table_lengths() {
+        assert_eq!(table_len::<u64>(1), 1 << 17);
+        assert_eq!(table_len::<[u8; 24]>(1), 1 << 15);
+        assert_eq!(table_len::<[u8; 24]>(0), 1);
+    }
+
+    /// Checks that `keys`, taken from `n` distinct hashes, land in about as many of `buckets`
+    /// buckets as uniformly random keys would.
+    fn check_buckets(keys: impl Iterator<Item = u64>, n: usize, buckets: u64) {
+        let occupied = keys.collect::<HashSet<_>>().len() as f64;
+        let load = n as f64 / buckets as f64;
+        let expected = buckets as f64 * (1.0 - (-load).exp());
+        assert!(
+            (occupied - expected).abs() < expected * 0.01,
+            "{occupied} buckets occupied, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn collisions() {
+        let hashes: Vec<_> = verify::legal_states::<3, 4>().map(State::hash64).collect();
+        let distinct: HashSet<_> = hashes.iter().collect();
+        assert_eq!(distinct.len(), hashes.len());
+
+        let n = hashes.len();
+        check_buckets(hashes.iter().map(|hash| hash & (1 << 20) - 1), n, 1 << 20);
+        check_buckets(hashes.iter().map(|hash| hash >> 44), n, 1 << 20);
+        check_buckets(hashes.iter().map(|hash| hash & (1 << 16) - 1), n, 1 << 16);
+    }
+
+    #[test]
+    fn wide_collisions() {
+        // Positions differing in the low and high words of a u128 in the same way.
+        let hashes: HashSet<_> = (0..1 << 12)
+            .map(|bits: u128| {
+                let state = State::<10, 10, u128> {
+                    me: bits | bits << 64,
+                    them: bits << 32,
+                };
+                state.hash64()
+            })
+            .collect();
+        assert_eq!(hashes.len(), 1 << 12);
+    }
+}
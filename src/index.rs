@@ -0,0 +1,273 @@
+//! Dense numbering of legal positions, for tables with an entry per position.
+//!
+//! Positions are grouped by [`Material`], the number of pieces of each side. Within a class, the
+//! side to move's pieces are ranked among the squares off its last row, and the opponent's among
+//! the squares left over, both in the combinatorial number system. Classes are numbered one after
+//! another, so [`index`] is a bijection from the legal positions onto `0..len()`, where legal
+//! means the same as in [`verify::legal_states`](crate::solver::verify::legal_states).
+//!
+//! Counts and indices are `u64`s, which limits [`index`] to boards of up to about 36 squares, and
+//! [`rank`] to material classes with fewer positions than that. Functions panic when the count
+//! doesn't fit, in every build; [`checked_positions`] and [`checked_len`] return `None` instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{index::{self, Material}, State};
+//! let state = State::<3, 4>::default();
+//! assert_eq!(index::unindex::<3, 4, u64>(index::index(&state)), state);
+//! assert_eq!(index::positions::<3, 4>(Material { mine: 1, theirs: 1 }), 9 * 11);
+//! ```
+
+use crate::{Bitboard, State};
+
+/// The number of pieces of each side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Material {
+    pub mine: u32,
+    pub theirs: u32,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> State<WIDTH, HEIGHT, B> {
+    pub fn material(self) -> Material {
+        Material {
+            mine: self.me.count_ones(),
+            theirs: self.them.count_ones(),
+        }
+    }
+}
+
+/// Pascal's triangle up to the largest board, saturating where the coefficients don't fit.
+static BINOMIALS: [[u64; 129]; 129] = {
+    let mut table = [[0u64; 129]; 129];
+    let mut n = 0;
+    while n < 129 {
+        table[n][0] = 1;
+        let mut k = 1;
+        while k <= n {
+            table[n][k] = table[n - 1][k - 1].saturating_add(table[n - 1][k]);
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+};
+
+/// The binomial coefficient `n` choose `k`, or `None` if it doesn't fit in a `u64`.
+#[inline]
+fn checked_binomial(n: u32, k: u32) -> Option<u64> {
+    if k > n {
+        return Some(0);
+    }
+    Some(BINOMIALS[n as usize][k as usize]).filter(|&binomial| binomial != u64::MAX)
+}
+
+/// The binomial coefficient `n` choose `k`. Panics if it doesn't fit in a `u64`.
+#[inline]
+fn binomial(n: u32, k: u32) -> u64 {
+    checked_binomial(n, k).expect("too many positions")
+}
+
+/// Ranks the set bits of `board` in the combinatorial number system.
+fn rank_bits<B: Bitboard>(board: B) -> u64 {
+    board
+        .bits()
+        .zip(1..)
+        .try_fold(0u64, |rank, ((square, _), i)| {
+            rank.checked_add(binomial(square, i))
+        })
+        .expect("too many positions")
+}
+
+/// The set of `count` of the first `squares` squares with rank `rank`, the inverse of
+/// [`rank_bits`].
+fn unrank_bits<B: Bitboard>(mut rank: u64, count: u32, squares: u32) -> B {
+    let mut board = B::ZERO;
+    let mut square = squares;
+    for i in (1..=count).rev() {
+        square -= 1;
+        while binomial(square, i) > rank {
+            square -= 1;
+        }
+        rank -= binomial(square, i);
+        board = board | B::ONE << square;
+    }
+    board
+}
+
+/// Every material class of the board, in index order.
+pub fn materials<const WIDTH: u32, const HEIGHT: u32>() -> impl Iterator<Item = Material> {
+    (0..=2 * WIDTH).flat_map(|mine| (0..=2 * WIDTH).map(move |theirs| Material { mine, theirs }))
+}
+
+/// The number of legal positions with `material`, or `None` if it doesn't fit in a `u64`.
+pub fn checked_positions<const WIDTH: u32, const HEIGHT: u32>(material: Material) -> Option<u64> {
+    let area = WIDTH * HEIGHT;
+    if material.mine > area - WIDTH {
+        return Some(0);
+    }
+    checked_binomial(area - WIDTH, material.mine)?
+        .checked_mul(checked_binomial(area - material.mine, material.theirs)?)
+}
+
+/// The number of legal positions with `material`. Panics if it doesn't fit in a `u64`.
+pub fn positions<const WIDTH: u32, const HEIGHT: u32>(material: Material) -> u64 {
+    checked_positions::<WIDTH, HEIGHT>(material).expect("too many positions")
+}
+
+/// The number of legal positions, or `None` if it doesn't fit in a `u64`.
+pub fn checked_len<const WIDTH: u32, const HEIGHT: u32>() -> Option<u64> {
+    materials::<WIDTH, HEIGHT>().try_fold(0u64, |len, material| {
+        len.checked_add(checked_positions::<WIDTH, HEIGHT>(material)?)
+    })
+}
+
+/// The number of legal positions. Panics if it doesn't fit in a `u64`.
+pub fn len<const WIDTH: u32, const HEIGHT: u32>() -> u64 {
+    checked_len::<WIDTH, HEIGHT>().expect("too many positions")
+}
+
+/// The index of `state` among the positions of its material class.
+pub fn rank<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+    state: &State<WIDTH, HEIGHT, B>,
+) -> u64 {
+    let area = State::<WIDTH, HEIGHT, B>::AREA;
+    let material = state.material();
+    debug_assert!(state.me >> area - WIDTH == B::ZERO, "piece on the last row");
+
+    // Number the squares not taken by the side to move consecutively.
+    let theirs = state.them.bits().fold(B::ZERO, |theirs, (square, bit)| {
+        let below = state.me & !(!B::ZERO << square);
+        theirs | bit >> below.count_ones()
+    });
+    rank_bits(state.me)
+        .checked_mul(binomial(area - material.mine, material.theirs))
+        .and_then(|rank| rank.checked_add(rank_bits(theirs)))
+        .expect("too many positions")
+}
+
+/// The position with `material` and index `rank` within its class, the inverse of [`rank`].
+pub fn unrank<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+    material: Material,
+    rank: u64,
+) -> State<WIDTH, HEIGHT, B> {
+    let area = State::<WIDTH, HEIGHT, B>::AREA;
+    let their_positions = binomial(area - material.mine, material.theirs);
+    let me: B = unrank_bits(rank / their_positions, material.mine, area - WIDTH);
+    let mut theirs: B = unrank_bits(
+        rank % their_positions,
+        material.theirs,
+        area - material.mine,
+    );
+
+    // Spread the opponent's pieces back over the squares not taken by the side to move.
+    let mut them = B::ZERO;
+    let mut free = 0;
+    for square in 0..area {
+        if me >> square & B::ONE == B::ZERO {
+            if theirs >> free & B::ONE != B::ZERO {
+                them = them | B::ONE << square;
+                theirs = theirs ^ B::ONE << free;
+            }
+            free += 1;
+        }
+    }
+    State { me, them }
+}
+
+/// The index of `state` among all legal positions.
+pub fn index<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+    state: &State<WIDTH, HEIGHT, B>,
+) -> u64 {
+    let material = state.material();
+    materials::<WIDTH, HEIGHT>()
+        .take_while(|&m| m != material)
+        .try_fold(rank(state), |index, material| {
+            index.checked_add(positions::<WIDTH, HEIGHT>(material))
+        })
+        .expect("too many positions")
+}
+
+/// The legal position with index `index`, the inverse of [`index`]. Panics unless
+/// `index < len()`.
+pub fn unindex<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+    mut index: u64,
+) -> State<WIDTH, HEIGHT, B> {
+    for material in materials::<WIDTH, HEIGHT>() {
+        let count = positions::<WIDTH, HEIGHT>(material);
+        if index < count {
+            return unrank(material, index);
+        }
+        index -= count;
+    }
+    panic!("index out of range");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::verify;
+    use std::collections::HashMap;
+
+    #[test]
+    fn binomials() {
+        assert_eq!(binomial(5, 2), 10);
+        assert_eq!(binomial(5, 6), 0);
+        assert_eq!(binomial(0, 0), 1);
+        assert_eq!(binomial(64, 32), 1_832_624_140_942_590_534);
+        assert_eq!(binomial(128, 1), 128);
+        assert_eq!(binomial(100, 98), 4950);
+    }
+
+    #[test]
+    fn bijection() {
+        let mut counts = HashMap::new();
+        let mut seen = vec![false; len::<3, 4>() as usize];
+        for state in verify::legal_states::<3, 4>() {
+            let i = index(&state);
+            assert!(!seen[i as usize], "{state:?}");
+            seen[i as usize] = true;
+            assert_eq!(unindex::<3, 4, u64>(i), state);
+            *counts.entry(state.material()).or_insert(0) += 1;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        for material in materials::<3, 4>() {
+            assert_eq!(
+                counts[&material],
+                positions::<3, 4>(material),
+                "{material:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn wide_boards() {
+        let material = Material { mine: 3, theirs: 4 };
+        let last = positions::<10, 10>(material) - 1;
+        for rank in [0, 1, 12345, last / 2, last] {
+            let state = unrank::<10, 10, u128>(material, rank);
+            assert_eq!(state.material(), material);
+            assert_eq!(super::rank(&state), rank);
+        }
+        assert_eq!(unrank::<10, 10, u128>(material, last).me >> 87, 0b111);
+    }
+
+    #[test]
+    fn overflow() {
+        let full = Material {
+            mine: 16,
+            theirs: 16,
+        };
+        assert_eq!(checked_positions::<8, 8>(full), None);
+        assert_eq!(checked_len::<8, 8>(), None);
+        assert_eq!(checked_len::<3, 4>(), Some(len::<3, 4>()));
+        assert_eq!(
+            checked_positions::<3, 4>(Material { mine: 1, theirs: 1 }),
+            Some(9 * 11)
+        );
+        let result = std::panic::catch_unwind(|| positions::<8, 8>(full));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(len::<8, 8>);
+        assert!(result.is_err());
+    }
+}
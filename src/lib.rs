@@ -9,6 +9,14 @@
 //! assert!(!is_winning);
 //! ```
 //!
+//! Boards of more than 64 squares need a wider bitboard:
+//!
+//! ```
+//! use breakthrough_anf::*;
+//! let ten_by_ten = State::<10, 10, u128>::default();
+//! assert_eq!(ten_by_ten.perft(2), 28 * 28);
+//! ```
+//!
 //! ```compile_fail
 //! use breakthrough_anf::*;
 //! let excessively_large = State::<50, 50>::default();
@@ -16,64 +24,252 @@
 //!
 //! ```compile_fail
 //! use breakthrough_anf::*;
+//! let excessively_large = State::<12, 11, u128>::default();
+//! ```
+//!
+//! ```compile_fail
+//! use breakthrough_anf::*;
 //! let not_tall_enough = State::<14, 3>::default();
 //! ```
 
-use bit_iter::BitIter;
+#![allow(clippy::precedence)]
+
+pub mod bitboard;
+pub mod eval;
+pub mod hash;
+pub mod index;
+pub mod mcts;
+pub mod notation;
+pub mod perft;
+pub mod position;
+pub mod search;
+pub mod solver;
+
+pub use bitboard::Bitboard;
+pub use notation::ParseStateError;
+
+/// A move of the side to move, with squares numbered in its own frame: square `i` is on row
+/// `i / WIDTH`, counting from the side to move's home row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: u32,
+    pub to: u32,
+    pub capture: bool,
+}
+
+/// Leaf counts of [`State::perft_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    /// Leaves reached by a capture.
+    pub captures: u64,
+    /// Leaves reached by a winning move, where the side to move has lost.
+    pub wins: u64,
+    /// Leaves where the side to move has no legal moves.
+    pub no_moves: u64,
+}
+
+/// A position, seen from the side to move, stored as one [`Bitboard`] per side.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct State<const WIDTH: u32, const HEIGHT: u32> {
-    me: u64,
-    them: u64,
+pub struct State<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    me: B,
+    them: B,
 }
 
-impl<const WIDTH: u32, const HEIGHT: u32> Default for State<WIDTH, HEIGHT> {
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Default for State<WIDTH, HEIGHT, B> {
     fn default() -> Self {
-        let me = Self::ROW_MASK | Self::ROW_MASK << WIDTH;
+        let row = B::from_u64(Self::ROW_MASK);
+        let me = row | row << WIDTH;
         let them = me << Self::AREA - 2 * WIDTH;
         Self { me, them }
     }
 }
 
-impl<const WIDTH: u32, const HEIGHT: u32> State<WIDTH, HEIGHT> {
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> State<WIDTH, HEIGHT, B> {
     pub const AREA: u32 = {
         let area = WIDTH * HEIGHT;
         assert!(HEIGHT >= 4);
-        assert!(area <= u64::BITS);
+        assert!(area <= B::BITS);
         area
     };
     pub const ROW_MASK: u64 = {
         let bit = 1 << (WIDTH - 1);
         (bit - 1) | bit
     };
+    /// The squares of the first file (column), as a `u128` so it can be computed for any
+    /// bitboard type.
+    const FILE_MASK: u128 = {
+        let mut mask = 0;
+        let mut row = 0;
+        while row < HEIGHT {
+            mask |= 1 << row * WIDTH;
+            row += 1;
+        }
+        mask
+    };
 
+    /// Returns the state from the opponent's perspective, with `them` to move.
     #[inline]
-    pub fn children(mut self) -> impl Iterator<Item = Self> {
-        // Generate moves from flipped perspective.
-        (self.them, self.me) = (
-            self.me.reverse_bits() >> (u64::BITS - Self::AREA),
-            self.them.reverse_bits() >> (u64::BITS - Self::AREA),
-        );
+    fn flipped(self) -> Self {
+        Self {
+            me: self.them.reverse_bits() >> B::BITS - Self::AREA,
+            them: self.me.reverse_bits() >> B::BITS - Self::AREA,
+        }
+    }
+
+    /// Returns the state reflected left to right, which has the same value and perft.
+    #[inline]
+    pub fn mirror(self) -> Self {
+        let first_file = B::from_u128(Self::FILE_MASK);
+        let mirror = |board: B| {
+            (0..WIDTH).fold(B::ZERO, |acc, col| {
+                acc | (board >> col & first_file) << WIDTH - 1 - col
+            })
+        };
+        Self {
+            me: mirror(self.me),
+            them: mirror(self.them),
+        }
+    }
+
+    /// Returns the same representative for a state and its mirror image, for keying tables.
+    #[inline]
+    pub fn canonical(self) -> Self {
+        let mirror = self.mirror();
+        if (mirror.me, mirror.them) < (self.me, self.them) {
+            mirror
+        } else {
+            self
+        }
+    }
+
+    /// Squares attacked by `pieces` moving towards the high bits, diagonally one row ahead.
+    #[inline]
+    fn attacks_up(pieces: B) -> B {
+        let first_file = B::from_u128(Self::FILE_MASK);
+        let last_file = first_file << WIDTH - 1;
+        let board = B::from_u128(u128::MAX >> 128 - Self::AREA);
+        ((pieces & !last_file) << WIDTH + 1 | (pieces & !first_file) << WIDTH - 1) & board
+    }
+
+    /// Squares attacked by `pieces` moving towards the low bits. Masking out the edge files keeps
+    /// diagonals from wrapping around to the other side of the board, and shifts of pieces on the
+    /// first row come out empty.
+    #[inline]
+    fn attacks_down(pieces: B) -> B {
+        let first_file = B::from_u128(Self::FILE_MASK);
+        let last_file = first_file << WIDTH - 1;
+        (pieces & !last_file) >> WIDTH - 1 | (pieces & !first_file) >> WIDTH + 1
+    }
 
-        BitIter::from(self.them).flat_map(move |(i, bit)| {
-            // TODO: Bit-based row mask discovery for non-power-of-two widths.
-            let row_mask = Self::ROW_MASK << (i / WIDTH - 1) * WIDTH;
+    /// The squares the side to move attacks, and could capture on.
+    #[inline]
+    pub fn attacks_mine(self) -> B {
+        Self::attacks_up(self.me)
+    }
+
+    /// The squares the opponent attacks.
+    #[inline]
+    pub fn attacks_theirs(self) -> B {
+        Self::attacks_down(self.them)
+    }
 
-            let diagonals = bit >> WIDTH - 1 | bit >> WIDTH + 1;
-            let forward = bit >> WIDTH & !self.me;
+    /// The pieces of the side to move that are attacked and not defended, so that they can be
+    /// captured without a recapture.
+    #[inline]
+    pub fn hanging(self) -> B {
+        self.me & self.attacks_theirs() & !self.attacks_mine()
+    }
 
-            let move_mask = (forward | diagonals) & !self.them & row_mask;
-            let new_them = self.them ^ bit;
+    #[inline]
+    pub fn moves(self) -> impl Iterator<Item = Move> {
+        self.moves_of(!B::ZERO)
+    }
 
-            BitIter::from(move_mask).map(move |(_, bit)| Self {
-                me: self.me & !bit,
-                them: new_them ^ bit,
+    /// The moves that reach the last row, winning immediately.
+    #[inline]
+    pub fn immediate_win_moves(self) -> impl Iterator<Item = Move> {
+        // Only pieces on the second to last row can reach it, and all of their moves do.
+        self.moves_of(B::from_u64(Self::ROW_MASK) << WIDTH)
+    }
+
+    /// The moves of the pieces of the side to move in `pieces`, given in the flipped frame.
+    #[inline]
+    fn moves_of(self, pieces: B) -> impl Iterator<Item = Move> {
+        // Generate moves from flipped perspective.
+        let flipped = self.flipped();
+
+        (flipped.them & pieces).bits().flat_map(move |(i, bit)| {
+            // Pieces move towards the low bits, one row down.
+            let forward = bit >> WIDTH & !flipped.me;
+            let move_mask = (forward | Self::attacks_down(bit)) & !flipped.them;
+
+            move_mask.bits().map(move |(j, bit)| Move {
+                from: Self::AREA - 1 - i,
+                to: Self::AREA - 1 - j,
+                capture: flipped.me & bit != B::ZERO,
             })
         })
     }
 
+    /// Plays `mv`, which must be legal, returning the state from the opponent's perspective.
+    #[inline]
+    pub fn apply(self, mv: Move) -> Self {
+        let flipped = self.flipped();
+        let from = B::ONE << Self::AREA - 1 - mv.from;
+        let to = B::ONE << Self::AREA - 1 - mv.to;
+        debug_assert_eq!(flipped.me & to != B::ZERO, mv.capture);
+
+        Self {
+            me: flipped.me & !to,
+            them: flipped.them ^ from ^ to,
+        }
+    }
+
+    #[inline]
+    pub fn children(self) -> impl Iterator<Item = Self> {
+        self.moves().map(move |mv| self.apply(mv))
+    }
+
+    /// Returns the positions with a move to this one, from their own perspective. Un-captures
+    /// put back a piece of the opponent's on the destination square, as long as it then has no
+    /// more pieces than it starts with. Parents that have already been lost, or have a piece on
+    /// their last row, are left out, since they have no moves.
+    pub fn parents(self) -> impl Iterator<Item = Self> {
+        // The frame of the side that just moved, whose pieces move towards the high bits.
+        let moved = self.flipped();
+        let row = B::from_u64(Self::ROW_MASK);
+        let empty = !(moved.me | moved.them);
+
+        // Only a piece that has just reached the last row can be on it.
+        let arrived = moved.me & row << Self::AREA - WIDTH;
+        let movable = match (moved.them & row != B::ZERO, arrived.count_ones()) {
+            (false, 0) => moved.me,
+            (false, 1) => arrived,
+            _ => B::ZERO,
+        };
+        let can_uncapture = moved.them.count_ones() < 2 * WIDTH;
+
+        movable.bits().flat_map(move |(_, bit)| {
+            let diagonals = Self::attacks_down(bit) & empty;
+            let forward = bit >> WIDTH & empty;
+
+            let quiet = (forward | diagonals).bits().map(move |(_, origin)| Self {
+                me: moved.me ^ bit ^ origin,
+                them: moved.them,
+            });
+            let captures = if can_uncapture { diagonals } else { B::ZERO };
+            let uncaptures = captures.bits().map(move |(_, origin)| Self {
+                me: moved.me ^ bit ^ origin,
+                them: moved.them | bit,
+            });
+            quiet.chain(uncaptures)
+        })
+    }
+
     #[inline]
     pub fn is_lost(self) -> bool {
-        self.them & Self::ROW_MASK != 0
+        self.them & B::from_u64(Self::ROW_MASK) != B::ZERO
     }
 
     pub fn perft(self, depth: u32) -> u64 {
@@ -83,32 +279,374 @@ impl<const WIDTH: u32, const HEIGHT: u32> State<WIDTH, HEIGHT> {
             self.children().map(|state| state.perft(depth - 1)).sum()
         }
     }
+
+    /// Counts the leaves at `depth` like [`perft`](Self::perft), broken down by how they were
+    /// reached. Like `perft`, this doesn't stop at finished games.
+    pub fn perft_stats(self, depth: u32) -> PerftStats {
+        let mut stats = PerftStats::default();
+        self.perft_stats_into(depth, false, &mut stats);
+        stats
+    }
+
+    fn perft_stats_into(self, depth: u32, capture: bool, stats: &mut PerftStats) {
+        if depth == 0 {
+            stats.nodes += 1;
+            stats.captures += capture as u64;
+            stats.wins += self.is_lost() as u64;
+            stats.no_moves += self.moves().next().is_none() as u64;
+        } else {
+            for mv in self.moves() {
+                self.apply(mv)
+                    .perft_stats_into(depth - 1, mv.capture, stats);
+            }
+        }
+    }
+
+    /// Counts the leaves under each move, for comparing move generators move by move.
+    pub fn perft_divide(self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        self.moves()
+            .map(|mv| (mv, self.apply(mv).perft(depth - 1)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bit_iter::BitIter;
+
+    fn perft_4x16<B: Bitboard>() {
+        let state = State::<4, 16, B>::default();
+        assert_eq!(state.perft(1), 10);
+        assert_eq!(state.perft(2), 100);
+        assert_eq!(state.perft(3), 1100);
+        assert_eq!(state.perft(4), 12100);
+        assert_eq!(state.perft(5), 146960);
+    }
+
+    /// The original single-pass generator that `children` was built from.
+    fn children_direct<const WIDTH: u32, const HEIGHT: u32>(
+        mut state: State<WIDTH, HEIGHT>,
+    ) -> Vec<State<WIDTH, HEIGHT>> {
+        (state.them, state.me) = (
+            state.me.reverse_bits() >> (u64::BITS - State::<WIDTH, HEIGHT>::AREA),
+            state.them.reverse_bits() >> (u64::BITS - State::<WIDTH, HEIGHT>::AREA),
+        );
+
+        BitIter::from(state.them)
+            .flat_map(move |(i, bit)| {
+                let row_mask = State::<WIDTH, HEIGHT>::ROW_MASK << (i / WIDTH - 1) * WIDTH;
+                let diagonals = bit >> WIDTH - 1 | bit >> WIDTH + 1;
+                let forward = bit >> WIDTH & !state.me;
+                let move_mask = (forward | diagonals) & !state.them & row_mask;
+                let new_them = state.them ^ bit;
+
+                BitIter::from(move_mask).map(move |(_, bit)| State {
+                    me: state.me & !bit,
+                    them: new_them ^ bit,
+                })
+            })
+            .collect()
+    }
+
+    fn check_moves<const WIDTH: u32, const HEIGHT: u32>(state: State<WIDTH, HEIGHT>, depth: u32) {
+        let children: Vec<_> = state.children().collect();
+        assert_eq!(children, children_direct(state));
+        for (mv, child) in state.moves().zip(&children) {
+            assert_eq!(child.me.count_ones() < state.them.count_ones(), mv.capture);
+            if depth > 0 && !child.is_lost() {
+                check_moves(*child, depth - 1);
+            }
+        }
+    }
 
     #[test]
-    fn perft_1() {
-        assert_eq!(State::<4, 16>::default().perft(1), 10);
+    fn moves_match_children() {
+        check_moves(State::<4, 16>::default(), 3);
+        check_moves(State::<3, 4>::default(), 6);
+        check_moves(State::<8, 8>::default(), 2);
+        check_moves(State::<7, 9>::default(), 2);
+    }
+
+    /// Checks that every move of a lone piece of the side to move, on an empty board and on one
+    /// full of enemy pieces, goes one row forward and at most one file sideways.
+    fn check_no_wrap<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>() {
+        let area = State::<WIDTH, HEIGHT, B>::AREA;
+        let board = B::from_u128(u128::MAX >> 128 - area);
+        for square in 0..area {
+            let me = B::ONE << square;
+            for them in [B::ZERO, board & !me] {
+                let state = State::<WIDTH, HEIGHT, B> { me, them };
+                let mut files = Vec::new();
+                for mv in state.moves() {
+                    assert_eq!(mv.from, square);
+                    assert_eq!(
+                        mv.to / WIDTH,
+                        square / WIDTH + 1,
+                        "{WIDTH}x{HEIGHT}: {mv:?}"
+                    );
+                    assert!(
+                        mv.to % WIDTH + 1 >= square % WIDTH,
+                        "{WIDTH}x{HEIGHT}: {mv:?}"
+                    );
+                    assert!(
+                        mv.to % WIDTH <= square % WIDTH + 1,
+                        "{WIDTH}x{HEIGHT}: {mv:?}"
+                    );
+                    files.push(mv.to % WIDTH);
+                }
+
+                let col = square % WIDTH;
+                let expected = if square / WIDTH == HEIGHT - 1 {
+                    0
+                } else {
+                    let sideways = (col > 0) as usize + (col < WIDTH - 1) as usize;
+                    sideways + (them == B::ZERO) as usize
+                };
+                assert_eq!(files.len(), expected, "{WIDTH}x{HEIGHT}: square {square}");
+            }
+        }
     }
 
     #[test]
-    fn perft_2() {
-        assert_eq!(State::<4, 16>::default().perft(2), 100);
+    fn moves_never_wrap() {
+        check_no_wrap::<1, 5, u32>();
+        check_no_wrap::<2, 7, u32>();
+        check_no_wrap::<3, 4, u32>();
+        check_no_wrap::<5, 6, u32>();
+        check_no_wrap::<6, 5, u32>();
+        check_no_wrap::<7, 9, u64>();
+        check_no_wrap::<8, 8, u64>();
+        check_no_wrap::<16, 4, u64>();
+        check_no_wrap::<9, 9, u128>();
+        check_no_wrap::<10, 12, u128>();
+        check_no_wrap::<11, 11, u128>();
+        check_no_wrap::<32, 4, u128>();
+    }
+
+    fn check_parents<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+        state: State<WIDTH, HEIGHT, B>,
+    ) {
+        let last_row = B::from_u64(State::<WIDTH, HEIGHT, B>::ROW_MASK)
+            << State::<WIDTH, HEIGHT, B>::AREA - WIDTH;
+        for parent in state.parents() {
+            assert!(
+                parent.children().any(|child| child == state),
+                "{parent:?} -> {state:?}"
+            );
+            assert!(parent.them.count_ones() <= 2 * WIDTH);
+            assert!(parent.me & last_row == B::ZERO && !parent.is_lost());
+        }
+        if !state.is_lost() && state.them.count_ones() <= 2 * WIDTH {
+            for child in state.children() {
+                assert!(
+                    child.parents().any(|parent| parent == state),
+                    "{state:?} -> {child:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parents() {
+        for state in solver::verify::legal_states::<3, 4>() {
+            check_parents(state);
+        }
+        let mut state = State::<7, 9, u128>::default();
+        for _ in 0..30 {
+            check_parents(state);
+            let Some(child) = state.children().nth(state.me.count_ones() as usize / 2) else {
+                break;
+            };
+            state = child;
+        }
+    }
+
+    /// Checks the attack helpers against their definitions, square by square.
+    fn check_attacks<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+        state: State<WIDTH, HEIGHT, B>,
+    ) {
+        let area = State::<WIDTH, HEIGHT, B>::AREA;
+        let has = |board: B, square: u32| board & B::ONE << square != B::ZERO;
+        let diagonal = |from: u32, to: u32, rows: i32| {
+            (to / WIDTH) as i32 - (from / WIDTH) as i32 == rows
+                && (to % WIDTH).abs_diff(from % WIDTH) == 1
+        };
+        for square in 0..area {
+            let mine = (0..area).any(|from| has(state.me, from) && diagonal(from, square, 1));
+            let theirs = (0..area).any(|from| has(state.them, from) && diagonal(from, square, -1));
+            assert_eq!(
+                has(state.attacks_mine(), square),
+                mine,
+                "{state:?}: {square}"
+            );
+            assert_eq!(
+                has(state.attacks_theirs(), square),
+                theirs,
+                "{state:?}: {square}"
+            );
+            assert_eq!(
+                has(state.hanging(), square),
+                has(state.me, square) && theirs && !mine,
+                "{state:?}: {square}"
+            );
+        }
+        let wins: Vec<_> = state
+            .moves()
+            .filter(|&mv| state.apply(mv).is_lost())
+            .collect();
+        assert_eq!(state.immediate_win_moves().collect::<Vec<_>>(), wins);
+    }
+
+    /// Checks the attack helpers along a game played by picking varied moves.
+    fn check_attacks_in_game<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>() {
+        let mut state = State::<WIDTH, HEIGHT, B>::default();
+        for ply in 0.. {
+            check_attacks(state);
+            let children: Vec<_> = state.children().collect();
+            if state.is_lost() || children.is_empty() {
+                break;
+            }
+            state = children[ply * 7 % children.len()];
+        }
     }
 
     #[test]
-    fn perft_3() {
-        assert_eq!(State::<4, 16>::default().perft(3), 1100);
+    fn attacks() {
+        for state in crate::solver::verify::legal_states::<3, 4>().step_by(7) {
+            check_attacks(state);
+        }
+        check_attacks_in_game::<1, 5, u32>();
+        check_attacks_in_game::<2, 7, u32>();
+        check_attacks_in_game::<5, 6, u32>();
+        check_attacks_in_game::<8, 8, u64>();
+        check_attacks_in_game::<16, 4, u64>();
+        check_attacks_in_game::<11, 11, u128>();
     }
 
     #[test]
-    fn perft_4() {
-        assert_eq!(State::<4, 16>::default().perft(4), 12100);
+    fn apply_moves_pieces() {
+        let state = State::<4, 5>::default();
+        let mv = state.moves().find(|mv| mv.from == 5 && mv.to == 9).unwrap();
+        assert!(!mv.capture);
+        // Seen from the opponent, the piece moves from their fourth row to their third.
+        let child = state.apply(mv);
+        assert_eq!(child.me, state.me);
+        assert_eq!(child.them, state.them ^ 1 << 14 ^ 1 << 10);
     }
 
-    // TODO: Perft 5.
-    // TODO: Combat perft.
+    // A u32 only holds the boards of up to 32 squares.
+    #[test]
+    fn perft_u32() {
+        perft_stats_small::<u32>();
+        assert_eq!(
+            State::<4, 8, u32>::default().perft(4),
+            State::<4, 8>::default().perft(4)
+        );
+    }
+
+    #[test]
+    fn perft_u64() {
+        perft_4x16::<u64>();
+        perft_stats_4x16::<u64>();
+        perft_stats_8x8::<u64>();
+        perft_stats_16x4::<u64>();
+        perft_stats_small::<u64>();
+    }
+
+    #[test]
+    fn perft_u128() {
+        perft_4x16::<u128>();
+        perft_stats_4x16::<u128>();
+        perft_stats_8x8::<u128>();
+        perft_stats_16x4::<u128>();
+        perft_stats_small::<u128>();
+    }
+
+    #[test]
+    fn wide_bitboard() {
+        // The armies can't meet within two plies, so each side's moves are independent.
+        assert_eq!(State::<11, 11, u128>::default().perft(2), 31 * 31);
+        assert_eq!(State::<10, 12, u128>::default().perft(2), 28 * 28);
+    }
+
+    #[test]
+    fn mirror() {
+        let state = State::<5, 6>::default();
+        assert_eq!(state.mirror(), state);
+        for child in state.children() {
+            let mirror = child.mirror();
+            assert_eq!(mirror.mirror(), child);
+            assert_eq!(mirror.canonical(), child.canonical());
+            assert_eq!(child.canonical().canonical(), child.canonical());
+            assert_eq!(mirror.perft(4), child.perft(4));
+        }
+
+        let mut state = State::<7, 5, u128>::default();
+        for _ in 0..6 {
+            state = state.children().last().unwrap();
+            assert_eq!(state.mirror().perft(3), state.perft(3));
+            assert_eq!(state.mirror().perft_stats(2), state.perft_stats(2));
+        }
+        let state: State<3, 4> = "W.W/1W1/B2/.BB w".parse().unwrap();
+        assert_eq!(state.mirror(), "W.W/1W1/2B/BB. w".parse().unwrap());
+    }
+
+    #[test]
+    fn perft_divide() {
+        let divide = State::<4, 16>::default().perft_divide(3);
+        assert_eq!(divide.len(), 10);
+        assert_eq!(divide.iter().map(|&(_, count)| count).sum::<u64>(), 1100);
+        assert!(State::<4, 16>::default().perft_divide(0).is_empty());
+    }
+
+    fn stats(nodes: u64, captures: u64, wins: u64, no_moves: u64) -> PerftStats {
+        PerftStats {
+            nodes,
+            captures,
+            wins,
+            no_moves,
+        }
+    }
+
+    fn perft_stats_4x16<B: Bitboard>() {
+        let state = State::<4, 16, B>::default();
+        assert_eq!(state.perft_stats(4), stats(12100, 0, 0, 0));
+    }
+
+    fn perft_stats_8x8<B: Bitboard>() {
+        let state = State::<8, 8, B>::default();
+        assert_eq!(state.perft_stats(1), stats(22, 0, 0, 0));
+        assert_eq!(state.perft_stats(2), stats(484, 0, 0, 0));
+        assert_eq!(state.perft_stats(3), stats(11132, 0, 0, 0));
+        assert_eq!(state.perft_stats(4), stats(256036, 0, 0, 0));
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn perft_stats_8x8_deep() {
+        let state = State::<8, 8>::default();
+        assert_eq!(state.perft_stats(5), stats(6182818, 934, 0, 0));
+    }
+
+    fn perft_stats_16x4<B: Bitboard>() {
+        let state = State::<16, 4, B>::default();
+        assert_eq!(state.perft_stats(1), stats(30, 30, 0, 0));
+        assert_eq!(state.perft_stats(2), stats(930, 872, 0, 0));
+        assert_eq!(state.perft_stats(3), stats(30454, 26298, 1684, 0));
+    }
+
+    fn perft_stats_small<B: Bitboard>() {
+        assert_eq!(
+            State::<4, 4, B>::default().perft_stats(4),
+            stats(2632, 1592, 364, 0)
+        );
+        assert_eq!(
+            State::<1, 5, B>::default().perft_stats(1),
+            stats(1, 0, 0, 1)
+        );
+    }
 }
@@ -1,6 +1,156 @@
-use breakthrough_anf::*;
-use normform::{Anf, BitTerm128 as Term};
+use breakthrough_anf::{
+    perft::PerftTable,
+    position::{square_name, Position},
+    Bitboard,
+};
+use std::{env, process::ExitCode, time::Instant};
 
-fn main() {
-    println!("Hello, world!");
+const USAGE: &str = "\
+usage: breakthrough_anf <command> <depth> [options]
+
+commands:
+    perft     count the leaves at <depth>
+    divide    count the leaves at <depth> under each move
+
+options:
+    --size <W>x<H>       board size, 2x4 to 8x8, 4x16, 9x9, 10x10 or 11x11
+                         [default: 8x8]
+    --position <FEN>     starting position [default: the initial position]
+    --hash <MiB>         memoize perft subtrees in a table of this size, per thread [default: 0]
+    --threads <N>        number of threads for perft [default: 1]";
+
+struct Args {
+    command: String,
+    depth: u32,
+    size: (u32, u32),
+    position: Option<String>,
+    hash: usize,
+    threads: usize,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args().skip(1);
+    let command = args.next().ok_or("missing command")?;
+    let depth = args.next().ok_or("missing depth")?;
+    let depth = depth
+        .parse()
+        .map_err(|_| format!("invalid depth {depth:?}"))?;
+
+    let mut size = (8, 8);
+    let mut position = None;
+    let mut hash = 0;
+    let mut threads = 1;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--size" => {
+                let value = value()?;
+                size = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or(format!("invalid size {value:?}"))?;
+            }
+            "--position" => position = Some(value()?),
+            "--hash" => {
+                let value = value()?;
+                hash = value
+                    .parse()
+                    .map_err(|_| format!("invalid hash size {value:?}"))?;
+            }
+            "--threads" => {
+                let value = value()?;
+                threads = value
+                    .parse()
+                    .ok()
+                    .filter(|&threads| threads > 0)
+                    .ok_or(format!("invalid thread count {value:?}"))?;
+            }
+            _ => return Err(format!("unknown option {arg:?}")),
+        }
+    }
+
+    Ok(Args {
+        command,
+        depth,
+        size,
+        position,
+        hash,
+        threads,
+    })
+}
+
+fn run<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(args: &Args) -> Result<(), String> {
+    let position: Position<WIDTH, HEIGHT, B> = match &args.position {
+        Some(fen) => fen.parse().map_err(|e| format!("invalid position: {e}"))?,
+        None => Position::default(),
+    };
+
+    let start = Instant::now();
+    let nodes = match args.command.as_str() {
+        "perft" => {
+            let state = position.state();
+            match (args.threads, args.hash) {
+                (1, 0) => state.perft(args.depth),
+                (1, hash) => state.perft_hashed(args.depth, &mut PerftTable::with_megabytes(hash)),
+                (threads, 0) => state.perft_parallel(args.depth, threads),
+                (threads, hash) => state.perft_parallel_hashed(args.depth, threads, hash),
+            }
+        }
+        "divide" => {
+            let mut divide: Vec<_> = position
+                .perft_divide(args.depth)
+                .into_iter()
+                .map(|(mv, count)| {
+                    let name = square_name::<WIDTH>(mv.from) + &square_name::<WIDTH>(mv.to);
+                    (name, count)
+                })
+                .collect();
+            divide.sort();
+            for (name, count) in &divide {
+                println!("{name}: {count}");
+            }
+            println!();
+            divide.iter().map(|(_, count)| count).sum()
+        }
+        command => return Err(format!("unknown command {command:?}")),
+    };
+
+    println!("Nodes searched: {nodes}");
+    eprintln!("Time: {:?}", start.elapsed());
+    Ok(())
+}
+
+macro_rules! dispatch {
+    ($args:expr, $($bits:ty: $($width:literal x $height:literal),*);*) => {
+        match $args.size {
+            $($(($width, $height) => run::<$width, $height, $bits>(&$args),)*)*
+            (width, height) => Err(format!("unsupported size {width}x{height}")),
+        }
+    };
+}
+
+fn main() -> ExitCode {
+    let result = parse_args().and_then(|args| {
+        dispatch!(args,
+            u64:
+            2 x 4, 2 x 5, 2 x 6, 2 x 7, 2 x 8,
+            3 x 4, 3 x 5, 3 x 6, 3 x 7, 3 x 8,
+            4 x 4, 4 x 5, 4 x 6, 4 x 7, 4 x 8,
+            5 x 4, 5 x 5, 5 x 6, 5 x 7, 5 x 8,
+            6 x 4, 6 x 5, 6 x 6, 6 x 7, 6 x 8,
+            7 x 4, 7 x 5, 7 x 6, 7 x 7, 7 x 8,
+            8 x 4, 8 x 5, 8 x 6, 8 x 7, 8 x 8,
+            4 x 16;
+            u128:
+            9 x 9, 10 x 10, 11 x 11
+        )
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
 }
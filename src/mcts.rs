@@ -0,0 +1,500 @@
+//! A Monte Carlo tree search player, for boards too large to solve.
+//!
+//! [`Mcts`] grows a tree from the position to play by UCT: it descends by the UCB1 formula,
+//! expands the leaf it reaches, plays a game out from there, and credits the result to the nodes
+//! on the way. Playouts are either uniformly random or heavy, taking wins and preferring captures.
+//!
+//! As in MCTS-solver, positions whose value is certain are marked proven and their values
+//! propagated up the tree: a node is won if any child is lost for the side to move there, and
+//! lost if all of its children are won. Proven nodes are not played out again, and the search
+//! stops early once the root is proven.
+//!
+//! The tree is kept between searches, and reused when the next position to search is in it, like
+//! after playing the best move and an opponent's reply.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{mcts::{Config, Mcts}, State};
+//! let mut mcts = Mcts::new(Config::default());
+//! let state = State::<6, 6>::default();
+//! let result = mcts.search(state, 1000);
+//! assert!(state.moves().any(|mv| Some(mv) == result.best_move));
+//! assert_eq!(result.proven, None);
+//! ```
+
+use crate::{hash::mix, solver::Player, Bitboard, Move, State};
+
+const NONE: u32 = u32::MAX;
+
+/// How games are played out from a new leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Playout {
+    /// Uniformly random moves.
+    Random,
+    /// A winning move when there is one, and otherwise a capture half of the time.
+    Heavy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    pub playout: Playout,
+    /// The weight of the exploration term of UCB1.
+    pub exploration: f64,
+    /// Seeds the playouts, which are deterministic for a given seed.
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            playout: Playout::Heavy,
+            exploration: std::f64::consts::SQRT_2,
+            seed: 0,
+        }
+    }
+}
+
+/// The result of a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsResult {
+    /// The best move, or `None` if the game is over.
+    pub best_move: Option<Move>,
+    /// The fraction of playouts through the best move won by the side to move.
+    pub value: f64,
+    /// The number of playouts through the root, including those of earlier searches reused.
+    pub visits: u32,
+    /// The winner, if the search has proven it.
+    pub proven: Option<Player>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node<const WIDTH: u32, const HEIGHT: u32, B> {
+    state: State<WIDTH, HEIGHT, B>,
+    /// The move leading here, or `None` at the root.
+    mv: Option<Move>,
+    parent: u32,
+    first_child: u32,
+    children: u32,
+    visits: u32,
+    /// Playouts won by the side that moved here.
+    wins: f64,
+    /// The winner relative to the side to move here, once proven.
+    proven: Option<Player>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Node<WIDTH, HEIGHT, B> {
+    fn new(state: State<WIDTH, HEIGHT, B>, mv: Option<Move>, parent: u32) -> Self {
+        Self {
+            state,
+            mv,
+            parent,
+            first_child: NONE,
+            children: 0,
+            visits: 0,
+            wins: 0.0,
+            proven: state.is_lost().then_some(Player::Opponent),
+        }
+    }
+
+    fn is_expanded(&self) -> bool {
+        self.first_child != NONE
+    }
+}
+
+/// A SplitMix64 generator, for playouts.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    /// A uniformly random number below `n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() as u128 * n as u128 >> 64) as usize
+    }
+}
+
+/// A Monte Carlo tree search player, keeping its tree across searches.
+#[derive(Debug, Clone)]
+pub struct Mcts<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    config: Config,
+    /// The tree, with the root first and each node's children next to each other.
+    nodes: Vec<Node<WIDTH, HEIGHT, B>>,
+    rng: Rng,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Mcts<WIDTH, HEIGHT, B> {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            nodes: Vec::new(),
+            rng: Rng(config.seed),
+        }
+    }
+
+    /// The number of nodes in the tree.
+    pub fn tree_size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Forgets the tree.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Runs `iterations` playouts from `state`, or fewer if it gets proven first, reusing the
+    /// part of the tree under `state` if it is the root or within two plies of it. The root is
+    /// expanded even without playouts, so there is a move to play.
+    pub fn search(&mut self, state: State<WIDTH, HEIGHT, B>, iterations: u64) -> MctsResult {
+        self.reroot(state);
+        if !self.nodes[0].is_expanded() && self.nodes[0].proven.is_none() {
+            self.expand(0);
+        }
+        for _ in 0..iterations {
+            if self.nodes[0].proven.is_some() {
+                break;
+            }
+            self.iterate();
+        }
+        self.result()
+    }
+
+    fn children(&self, i: usize) -> std::ops::Range<usize> {
+        let node = &self.nodes[i];
+        node.first_child as usize..(node.first_child + node.children) as usize
+    }
+
+    /// Makes `state` the root, keeping its subtree if it is in the tree.
+    fn reroot(&mut self, state: State<WIDTH, HEIGHT, B>) {
+        if self.nodes.first().is_some_and(|root| root.state == state) {
+            return;
+        }
+        let mut found = None;
+        if !self.nodes.is_empty() {
+            'search: for child in self.children(0) {
+                if self.nodes[child].state == state {
+                    found = Some(child);
+                    break;
+                }
+                for grandchild in self.children(child) {
+                    if self.nodes[grandchild].state == state {
+                        found = Some(grandchild);
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        let Some(root) = found else {
+            self.nodes = vec![Node::new(state, None, NONE)];
+            return;
+        };
+        // Copy the subtree breadth first, which keeps siblings together.
+        let mut nodes = vec![Node {
+            mv: None,
+            parent: NONE,
+            ..self.nodes[root]
+        }];
+        let mut sources = vec![root];
+        let mut i = 0;
+        while i < nodes.len() {
+            if nodes[i].is_expanded() {
+                let children = self.children(sources[i]);
+                nodes[i].first_child = nodes.len() as u32;
+                for child in children {
+                    nodes.push(Node {
+                        parent: i as u32,
+                        ..self.nodes[child]
+                    });
+                    sources.push(child);
+                }
+            }
+            i += 1;
+        }
+        self.nodes = nodes;
+    }
+
+    /// Selects a leaf, expands it, plays a game out from it and backs the result up.
+    fn iterate(&mut self) {
+        let mut i = 0;
+        while self.nodes[i].is_expanded() && self.nodes[i].proven.is_none() {
+            i = self.select(i);
+        }
+        if self.nodes[i].proven.is_none() {
+            self.expand(i);
+        }
+        let winner = match self.nodes[i].proven {
+            Some(winner) => winner,
+            None => self.playout(self.nodes[i].state),
+        };
+        self.backpropagate(i, winner);
+    }
+
+    /// The child of `i` with the highest UCB1 score, skipping children proven won for the
+    /// opponent unless all of them are.
+    fn select(&self, i: usize) -> usize {
+        let log_visits = (self.nodes[i].visits.max(1) as f64).ln();
+        let score = |child: usize| {
+            let node = &self.nodes[child];
+            match node.proven {
+                Some(Player::Mover) => f64::NEG_INFINITY,
+                Some(Player::Opponent) => f64::INFINITY,
+                None if node.visits == 0 => f64::MAX,
+                None => {
+                    let visits = node.visits as f64;
+                    node.wins / visits + self.config.exploration * (log_visits / visits).sqrt()
+                }
+            }
+        };
+        self.children(i)
+            .max_by(|&a, &b| score(a).total_cmp(&score(b)))
+            .expect("expanded node without children")
+    }
+
+    /// Adds the children of `i`, proving it if the game ends there.
+    fn expand(&mut self, i: usize) {
+        let state = self.nodes[i].state;
+        let first_child = self.nodes.len() as u32;
+        for mv in state.moves() {
+            self.nodes
+                .push(Node::new(state.apply(mv), Some(mv), i as u32));
+        }
+        let children = self.nodes.len() as u32 - first_child;
+        let node = &mut self.nodes[i];
+        node.first_child = first_child;
+        node.children = children;
+        self.update_proof(i);
+    }
+
+    /// Proves `i` from its children, if they prove it.
+    fn update_proof(&mut self, i: usize) {
+        let mut children = self.children(i);
+        let proven = if children
+            .clone()
+            .all(|child| self.nodes[child].proven == Some(Player::Mover))
+        {
+            // Also a loss when there are no moves.
+            Some(Player::Opponent)
+        } else if children.any(|child| self.nodes[child].proven == Some(Player::Opponent)) {
+            Some(Player::Mover)
+        } else {
+            None
+        };
+        self.nodes[i].proven = proven;
+    }
+
+    /// Plays random moves from `state` until the game ends, returning the winner relative to the
+    /// side to move in `state`.
+    fn playout(&mut self, mut state: State<WIDTH, HEIGHT, B>) -> Player {
+        let mut mover = true;
+        let mut moves = Vec::new();
+        loop {
+            moves.clear();
+            moves.extend(state.moves());
+            if state.is_lost() || moves.is_empty() {
+                return if mover {
+                    Player::Opponent
+                } else {
+                    Player::Mover
+                };
+            }
+            let mv = match self.config.playout {
+                Playout::Random => moves[self.rng.below(moves.len())],
+                Playout::Heavy => {
+                    if let Some(win) = state.immediate_win_moves().next() {
+                        win
+                    } else {
+                        if moves.iter().any(|mv| mv.capture) && self.rng.next() & 1 == 0 {
+                            moves.retain(|mv| mv.capture);
+                        }
+                        moves[self.rng.below(moves.len())]
+                    }
+                }
+            };
+            state = state.apply(mv);
+            mover = !mover;
+        }
+    }
+
+    /// Credits a playout from `i` won by `winner`, relative to the side to move at `i`, to `i`
+    /// and its ancestors, and propagates proofs up.
+    fn backpropagate(&mut self, mut i: usize, mut winner: Player) {
+        let mut proving = self.nodes[i].proven.is_some();
+        loop {
+            let node = &mut self.nodes[i];
+            node.visits += 1;
+            if winner == Player::Opponent {
+                node.wins += 1.0;
+            }
+            if node.parent == NONE {
+                break;
+            }
+            i = node.parent as usize;
+            winner = match winner {
+                Player::Mover => Player::Opponent,
+                Player::Opponent => Player::Mover,
+            };
+            if proving {
+                self.update_proof(i);
+                proving = self.nodes[i].proven.is_some();
+            }
+        }
+    }
+
+    fn result(&self) -> MctsResult {
+        let root = &self.nodes[0];
+        // A proven win is played as such, and otherwise the most visited move not proven lost.
+        let best = self.children(0).max_by_key(|&child| {
+            let node = &self.nodes[child];
+            match node.proven {
+                Some(Player::Opponent) => (2, node.visits),
+                None => (1, node.visits),
+                Some(Player::Mover) => (0, node.visits),
+            }
+        });
+        MctsResult {
+            best_move: best.and_then(|child| self.nodes[child].mv),
+            value: best.map_or(0.0, |child| {
+                let node = &self.nodes[child];
+                node.wins / node.visits.max(1) as f64
+            }),
+            visits: root.visits,
+            proven: root.proven,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{exact::Solver, verify};
+
+    fn config(playout: Playout) -> Config {
+        Config {
+            playout,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn heavy_playouts_take_wins() {
+        // Any move but a win lets Black win next.
+        let state: State<4, 5> = "W3/3B/4/1W2/4 w".parse().unwrap();
+        assert!(state.immediate_win_moves().next().is_some());
+        for seed in 0..20 {
+            let mut mcts = Mcts::new(Config {
+                seed,
+                ..config(Playout::Heavy)
+            });
+            assert_eq!(mcts.playout(state), Player::Mover);
+        }
+    }
+
+    #[test]
+    fn select_skips_proven_losses() {
+        let mut mcts = Mcts::new(Config::default());
+        mcts.search(State::<4, 5>::default(), 0);
+        let children = mcts.children(0);
+        let last = children.end - 1;
+        for child in children.start..last {
+            mcts.nodes[child].proven = Some(Player::Mover);
+        }
+        assert_eq!(mcts.select(0), last);
+        mcts.nodes[0].visits = 100;
+        mcts.nodes[last].visits = 99;
+        assert_eq!(mcts.select(0), last);
+
+        // With every move lost, the root is lost, and any move may be selected.
+        mcts.nodes[last].proven = Some(Player::Mover);
+        mcts.update_proof(0);
+        assert_eq!(mcts.nodes[0].proven, Some(Player::Opponent));
+        assert!(mcts.children(0).contains(&mcts.select(0)));
+    }
+
+    #[test]
+    fn result_prefers_proofs() {
+        let mut mcts = Mcts::new(Config::default());
+        mcts.search(State::<4, 5>::default(), 0);
+        let children = mcts.children(0);
+        for (visits, child) in children.clone().enumerate() {
+            mcts.nodes[child].visits = visits as u32 + 1;
+            mcts.nodes[child].proven = Some(Player::Mover);
+        }
+
+        // A proven loss is played as the most visited move.
+        let busiest = children.end - 1;
+        assert_eq!(mcts.result().best_move, mcts.nodes[busiest].mv);
+
+        // A move not proven lost is preferred, however little visited.
+        mcts.nodes[children.start + 1].proven = None;
+        assert_eq!(mcts.result().best_move, mcts.nodes[children.start + 1].mv);
+
+        // And a proven win over everything else.
+        mcts.nodes[children.start].proven = Some(Player::Opponent);
+        assert_eq!(mcts.result().best_move, mcts.nodes[children.start].mv);
+    }
+
+    #[test]
+    fn proofs_match_solver() {
+        let mut solver = Solver::new();
+        let mut proven = 0;
+        for state in verify::legal_states::<3, 4>().step_by(499) {
+            let result = Mcts::new(Config::default()).search(state, 2000);
+            if let Some(winner) = result.proven {
+                assert_eq!(winner, solver.solve(state).winner, "{state:?}");
+                proven += 1;
+            }
+        }
+        assert!(proven > 0);
+
+        let state = State::<3, 4>::default();
+        let result = Mcts::new(Config::default()).search(state, 1_000_000);
+        assert_eq!(result.proven, Some(Player::Opponent));
+    }
+
+    #[test]
+    fn tree_reuse() {
+        let mut mcts = Mcts::new(Config::default());
+        let state = State::<6, 6>::default();
+        let result = mcts.search(state, 500);
+        assert_eq!(result.visits, 500);
+
+        let reply = state
+            .apply(result.best_move.unwrap())
+            .children()
+            .next()
+            .unwrap();
+        let reused = mcts.tree_size();
+        let result = mcts.search(reply, 500);
+        assert!(result.visits > 500);
+        assert!(mcts.tree_size() < reused + 500 * 20);
+
+        // Searching the same position again continues where the last search stopped.
+        assert_eq!(mcts.search(reply, 100).visits, result.visits + 100);
+        mcts.clear();
+        assert_eq!(mcts.search(reply, 100).visits, 100);
+    }
+
+    #[test]
+    fn deterministic() {
+        let state = State::<8, 8>::default();
+        for playout in [Playout::Random, Playout::Heavy] {
+            let first = Mcts::new(config(playout)).search(state, 300);
+            let second = Mcts::new(config(playout)).search(state, 300);
+            assert_eq!(first, second);
+            assert!(state.moves().any(|mv| Some(mv) == first.best_move));
+        }
+    }
+
+    #[test]
+    fn no_iterations() {
+        let state = State::<6, 6>::default();
+        let result = Mcts::new(Config::default()).search(state, 0);
+        assert!(state.moves().any(|mv| Some(mv) == result.best_move));
+        assert_eq!(result.visits, 0);
+    }
+}
@@ -0,0 +1,282 @@
+//! Text rendering and parsing of positions.
+//!
+//! Positions are written as one string of squares per row, starting from White's home row and
+//! separated by `/`, followed by the side to move. Each square is `W`, `B` or `.`, and a run of
+//! empty squares may also be written as its length. White starts on the first rows and moves
+//! towards the last ones.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::*;
+//! let state: State<4, 4> = "WWWW/WWWW/BBBB/BBBB w".parse().unwrap();
+//! assert_eq!(state, State::default());
+//! assert_eq!(state.to_string(), "4 BBBB\n3 BBBB\n2 WWWW\n1 WWWW\n  abcd\n");
+//! ```
+
+use crate::{Bitboard, State};
+use std::{error::Error, fmt, str::FromStr};
+
+/// Renders the board with White at the bottom, assuming White is to move. With the alternate flag
+/// (`{:#}`), Black is assumed to be to move, and the board is turned around to keep White at the
+/// bottom.
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> fmt::Display for State<WIDTH, HEIGHT, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (white, black) = if f.alternate() {
+            let flipped = self.flipped();
+            (flipped.me, flipped.them)
+        } else {
+            (self.me, self.them)
+        };
+
+        let margin = HEIGHT.to_string().len();
+        for row in (0..HEIGHT).rev() {
+            write!(f, "{:>margin$} ", row + 1)?;
+            for col in 0..WIDTH {
+                let bit = B::ONE << row * WIDTH + col;
+                let c = if white & bit != B::ZERO {
+                    'W'
+                } else if black & bit != B::ZERO {
+                    'B'
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
+        }
+
+        // Files with more than one letter are written downwards, one letter per line.
+        let files: Vec<_> = (0..WIDTH).map(file).collect();
+        let lines = files.last().map_or(0, String::len);
+        for line in 0..lines {
+            write!(f, "{:margin$} ", "")?;
+            for name in &files {
+                let c = (line + name.len())
+                    .checked_sub(lines)
+                    .map_or(' ', |i| char::from(name.as_bytes()[i]));
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the file (column) `col`: `a` to `z`, then `aa`, `ab` and so on.
+pub(crate) fn file(col: u32) -> String {
+    let mut name = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        n -= 1;
+        name.push(b'a' + (n % 26) as u8);
+        n /= 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+/// An error parsing a position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseStateError {
+    /// The position has no side to move, or trailing input after it.
+    Shape,
+    /// The number of rows doesn't match the board.
+    RowCount { expected: u32, found: u32 },
+    /// The number of squares in a row doesn't match the board. Rows are numbered from 1, and
+    /// counts past `expected + 1` are reported as `expected + 1`.
+    RowLength { row: u32, expected: u32, found: u32 },
+    /// A square is neither `W`, `B`, `.` nor a digit.
+    Square(char),
+    /// The side to move is neither `w` nor `b`.
+    Side(String),
+}
+
+impl fmt::Display for ParseStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Shape => write!(f, "expected a board followed by the side to move"),
+            Self::RowCount { expected, found } => {
+                write!(f, "expected {expected} rows, found {found}")
+            }
+            Self::RowLength {
+                row,
+                expected,
+                found,
+            } => {
+                write!(f, "expected {expected} squares in row {row}, found {found}")
+            }
+            Self::Square(c) => write!(f, "invalid square {c:?}, expected 'W', 'B', '.' or a digit"),
+            Self::Side(side) => write!(f, "invalid side to move {side:?}, expected \"w\" or \"b\""),
+        }
+    }
+}
+
+impl Error for ParseStateError {}
+
+/// Parses a position into the frame of the side to move, turning the board around if Black is to
+/// move.
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> FromStr for State<WIDTH, HEIGHT, B> {
+    type Err = ParseStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let (Some(board), Some(side), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(ParseStateError::Shape);
+        };
+
+        let rows: Vec<_> = board.split('/').collect();
+        if rows.len() != HEIGHT as usize {
+            return Err(ParseStateError::RowCount {
+                expected: HEIGHT,
+                found: rows.len() as u32,
+            });
+        }
+
+        let mut white = B::ZERO;
+        let mut black = B::ZERO;
+        // Counts are capped just past the width, which is enough to tell that a row is too long,
+        // so that long rows and runs can't overflow them.
+        let cap = |count: u32| count.min(WIDTH + 1);
+        for (row, squares) in (0..).zip(rows) {
+            let mut col = 0;
+            let mut empty = 0;
+            for c in squares.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    empty = cap(empty * 10 + digit);
+                    continue;
+                }
+                col = cap(col + empty);
+                empty = 0;
+
+                let bit = if col < WIDTH {
+                    B::ONE << row * WIDTH + col
+                } else {
+                    B::ZERO
+                };
+                match c {
+                    'W' => white = white | bit,
+                    'B' => black = black | bit,
+                    '.' => {}
+                    _ => return Err(ParseStateError::Square(c)),
+                }
+                col = cap(col + 1);
+            }
+            col = cap(col + empty);
+
+            if col != WIDTH {
+                return Err(ParseStateError::RowLength {
+                    row: row + 1,
+                    expected: WIDTH,
+                    found: col,
+                });
+            }
+        }
+
+        let state = Self {
+            me: white,
+            them: black,
+        };
+        match side {
+            "w" => Ok(state),
+            "b" => Ok(state.flipped()),
+            _ => Err(ParseStateError::Side(side.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_default() {
+        assert_eq!(
+            State::<3, 10>::default().to_string(),
+            "10 BBB\n 9 BBB\n 8 ...\n 7 ...\n 6 ...\n 5 ...\n 4 ...\n 3 ...\n 2 WWW\n 1 WWW\n   abc\n",
+        );
+    }
+
+    #[test]
+    fn parse_black_to_move() {
+        let state: State<3, 4> = "W.W/1W1/B2/.BB b".parse().unwrap();
+        assert_eq!(state, "WW./2W/1B1/B.B w".parse().unwrap());
+        assert_eq!(format!("{state:#}"), "4 .BB\n3 B..\n2 .W.\n1 W.W\n  abc\n");
+        assert_eq!(state.children().count(), 6);
+    }
+
+    #[test]
+    fn round_trip() {
+        for state in State::<4, 5>::default()
+            .children()
+            .flat_map(State::children)
+        {
+            let text: String = format!("{state}")
+                .lines()
+                .rev()
+                .skip(1)
+                .map(|line| &line[2..])
+                .collect::<Vec<_>>()
+                .join("/");
+            assert_eq!(format!("{text} w").parse(), Ok(state));
+        }
+    }
+
+    #[test]
+    fn wide_files() {
+        assert_eq!(file(0), "a");
+        assert_eq!(file(25), "z");
+        assert_eq!(file(26), "aa");
+        assert_eq!(file(27), "ab");
+        assert_eq!(file(26 + 26 * 26), "aaa");
+
+        let text = State::<30, 4, u128>::default().to_string();
+        let footer: Vec<_> = text.lines().skip(4).collect();
+        assert_eq!(
+            footer,
+            [
+                "                            aaaa",
+                "  abcdefghijklmnopqrstuvwxyzabcd"
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |s: &str| s.parse::<State<3, 4>>().unwrap_err();
+        assert_eq!(parse("WWW/WWW/BBB/BBB"), ParseStateError::Shape);
+        assert_eq!(parse("WWW/WWW/BBB/BBB w x"), ParseStateError::Shape);
+        assert_eq!(
+            parse("WWW/WWW/BBB w"),
+            ParseStateError::RowCount {
+                expected: 4,
+                found: 3
+            },
+        );
+        assert_eq!(
+            parse("WWW/WWW/B3/BBB w"),
+            ParseStateError::RowLength {
+                row: 3,
+                expected: 3,
+                found: 4
+            },
+        );
+        let too_long = ParseStateError::RowLength {
+            row: 1,
+            expected: 3,
+            found: 4,
+        };
+        assert_eq!(parse("99999999999/3/3/3 w"), too_long);
+        assert_eq!(parse("4294967295W/3/3/3 w"), too_long);
+        assert_eq!(parse(&format!("{}/3/3/3 w", ".".repeat(1000))), too_long);
+        assert_eq!(parse("WWW/WxW/BBB/BBB w"), ParseStateError::Square('x'));
+        assert_eq!(
+            parse("WWW/WWW/BBB/BBB white"),
+            ParseStateError::Side("white".into())
+        );
+        assert_eq!(
+            parse("WWW/WWW/BBB/BBB 1").to_string(),
+            "invalid side to move \"1\", expected \"w\" or \"b\"",
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Perft accelerated by a transposition table.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{perft::PerftTable, State};
+//! let mut table = PerftTable::new(1 << 16);
+//! assert_eq!(State::<4, 16>::default().perft_hashed(5, &mut table), 146960);
+//! assert_eq!(State::<4, 16>::default().perft_parallel(5, 4), 146960);
+//! ```
+
+use crate::{
+    hash::{mix, table_len},
+    Bitboard, State,
+};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Entry<const WIDTH: u32, const HEIGHT: u32, B> {
+    state: State<WIDTH, HEIGHT, B>,
+    /// Zero for an empty entry; depths below 2 are never stored.
+    depth: u32,
+    count: u64,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Entry<WIDTH, HEIGHT, B> {
+    const EMPTY: Self = Self {
+        state: State {
+            me: B::ZERO,
+            them: B::ZERO,
+        },
+        depth: 0,
+        count: 0,
+    };
+}
+
+/// A fixed-size, replace-always table of subtree leaf counts.
+#[derive(Debug, Clone)]
+pub struct PerftTable<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    entries: Vec<Entry<WIDTH, HEIGHT, B>>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> PerftTable<WIDTH, HEIGHT, B> {
+    /// Creates a table of `capacity` entries, rounded up to a power of two.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: vec![Entry::EMPTY; capacity.next_power_of_two()],
+        }
+    }
+
+    /// Creates a table using about `megabytes` MiB of memory.
+    pub fn with_megabytes(megabytes: usize) -> Self {
+        Self::new(table_len::<Entry<WIDTH, HEIGHT, B>>(megabytes))
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The slot for `hash`, a [`State::hash64`], at `depth`.
+    fn slot(&self, hash: u64, depth: u32) -> usize {
+        let key = hash ^ (depth as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        mix(key) as usize & self.entries.len() - 1
+    }
+
+    fn get(&self, hash: u64, state: State<WIDTH, HEIGHT, B>, depth: u32) -> Option<u64> {
+        let entry = self.entries[self.slot(hash, depth)];
+        (entry.depth == depth && entry.state == state).then_some(entry.count)
+    }
+
+    fn insert(&mut self, hash: u64, state: State<WIDTH, HEIGHT, B>, depth: u32, count: u64) {
+        let slot = self.slot(hash, depth);
+        self.entries[slot] = Entry {
+            state,
+            depth,
+            count,
+        };
+    }
+
+    /// Forgets every entry.
+    pub fn clear(&mut self) {
+        self.entries.fill(Entry::EMPTY);
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> State<WIDTH, HEIGHT, B> {
+    /// Equivalent to [`perft`](Self::perft), with subtree counts memoized in `table`.
+    pub fn perft_hashed(self, depth: u32, table: &mut PerftTable<WIDTH, HEIGHT, B>) -> u64 {
+        match depth {
+            0 => 1,
+            1 => self.moves().count() as u64,
+            _ => {
+                // A position and its mirror image share an entry.
+                let key = self.canonical();
+                let hash = key.hash64();
+                if let Some(count) = table.get(hash, key, depth) {
+                    return count;
+                }
+                let count = self
+                    .children()
+                    .map(|child| child.perft_hashed(depth - 1, table))
+                    .sum();
+                table.insert(hash, key, depth, count);
+                count
+            }
+        }
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> State<WIDTH, HEIGHT, B> {
+    /// Equivalent to [`perft`](Self::perft), split across `threads` threads.
+    pub fn perft_parallel(self, depth: u32, threads: usize) -> u64 {
+        self.split(depth, threads, || State::perft)
+    }
+
+    /// Equivalent to [`perft_hashed`](Self::perft_hashed), split across `threads` threads, each
+    /// with its own table of about `megabytes` MiB.
+    pub fn perft_parallel_hashed(self, depth: u32, threads: usize, megabytes: usize) -> u64 {
+        self.split(depth, threads, || {
+            let mut table = PerftTable::with_megabytes(megabytes);
+            move |state: Self, depth| state.perft_hashed(depth, &mut table)
+        })
+    }
+
+    /// Expands the tree breadth-first until there are enough subtrees to keep `threads` threads
+    /// busy, then hands them out one at a time to workers made by `worker`.
+    fn split<F>(self, depth: u32, threads: usize, worker: impl Fn() -> F + Sync) -> u64
+    where
+        F: FnMut(Self, u32) -> u64,
+    {
+        let threads = threads.max(1);
+        let mut frontier = vec![self];
+        let mut remaining = depth;
+        while remaining > 0 && frontier.len() < 16 * threads {
+            frontier = frontier.into_iter().flat_map(State::children).collect();
+            remaining -= 1;
+        }
+
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut perft = worker();
+                        let mut count = 0;
+                        while let Some(&state) = frontier.get(next.fetch_add(1, Ordering::Relaxed))
+                        {
+                            count += perft(state, remaining);
+                        }
+                        count
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).sum()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_perft() {
+        let mut table = PerftTable::new(1 << 12);
+        for depth in 1..=5 {
+            let state = State::<4, 16>::default();
+            assert_eq!(state.perft_hashed(depth, &mut table), state.perft(depth));
+        }
+        let mut table = PerftTable::new(1 << 12);
+        for depth in 1..=5 {
+            let state = State::<4, 4>::default();
+            assert_eq!(state.perft_hashed(depth, &mut table), state.perft(depth));
+        }
+    }
+
+    #[test]
+    fn tiny_table() {
+        let mut table = PerftTable::new(3);
+        assert_eq!(table.capacity(), 4);
+        let state = State::<8, 8>::default();
+        assert_eq!(state.perft_hashed(4, &mut table), 256036);
+        table.clear();
+        assert_eq!(state.perft_hashed(4, &mut table), 256036);
+    }
+
+    #[test]
+    fn parallel() {
+        for threads in [1, 3, 8] {
+            for depth in 0..=5 {
+                let state = State::<4, 16>::default();
+                assert_eq!(state.perft_parallel(depth, threads), state.perft(depth));
+            }
+            let state = State::<8, 8>::default();
+            assert_eq!(state.perft_parallel_hashed(4, threads, 1), 256036);
+        }
+    }
+
+    #[test]
+    fn megabytes() {
+        let capacity = PerftTable::<8, 8>::with_megabytes(1).capacity();
+        assert!(capacity * std::mem::size_of::<Entry<8, 8, u64>>() <= 1 << 20);
+        assert!(capacity * std::mem::size_of::<Entry<8, 8, u64>>() > 1 << 19);
+    }
+}
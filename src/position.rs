@@ -0,0 +1,255 @@
+//! Positions with an absolute orientation.
+//!
+//! `State` always sees the board from the side to move, turning it around every ply. `Position`
+//! remembers which colour that is, so that boards, moves and game records can be shown the same
+//! way throughout a game. Squares are numbered from White's side: square `i` is on row
+//! `i / WIDTH`, where White starts on the first rows.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::position::{Color, Position};
+//! let mut position = Position::<3, 5>::default();
+//! for name in ["b2-b3", "c4-c3", "b3xa4"] {
+//!     let mv = position.parse_move(name).unwrap();
+//!     assert_eq!(position.move_name(mv), name);
+//!     position = position.play(mv);
+//! }
+//! assert_eq!(position.to_move(), Color::Black);
+//! assert_eq!(position.ply(), 3);
+//! ```
+
+use crate::{notation, Bitboard, Move, ParseStateError, State};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub fn opponent(self) -> Self {
+        match self {
+            Self::White => Self::Black,
+            Self::Black => Self::White,
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::White => write!(f, "White"),
+            Self::Black => write!(f, "Black"),
+        }
+    }
+}
+
+/// A `State` together with the colour to move and the number of plies played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    state: State<WIDTH, HEIGHT, B>,
+    to_move: Color,
+    ply: u32,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Hash for Position<WIDTH, HEIGHT, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.state.hash(state);
+        self.to_move.hash(state);
+        self.ply.hash(state);
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Default for Position<WIDTH, HEIGHT, B> {
+    fn default() -> Self {
+        Self::new(State::default(), Color::White)
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Position<WIDTH, HEIGHT, B> {
+    /// Starts a game from `state`, which is seen from `to_move`.
+    pub fn new(state: State<WIDTH, HEIGHT, B>, to_move: Color) -> Self {
+        Self {
+            state,
+            to_move,
+            ply: 0,
+        }
+    }
+
+    pub fn state(self) -> State<WIDTH, HEIGHT, B> {
+        self.state
+    }
+
+    pub fn to_move(self) -> Color {
+        self.to_move
+    }
+
+    /// The number of plies played since the start of the game.
+    pub fn ply(self) -> u32 {
+        self.ply
+    }
+
+    /// The pieces of `color`.
+    pub fn pieces(self, color: Color) -> B {
+        let state = match self.to_move {
+            Color::White => self.state,
+            Color::Black => self.state.flipped(),
+        };
+        match color {
+            Color::White => state.me,
+            Color::Black => state.them,
+        }
+    }
+
+    /// The winner, if the game is over. A side without legal moves has lost.
+    pub fn winner(self) -> Option<Color> {
+        (self.state.is_lost() || self.state.moves().next().is_none())
+            .then_some(self.to_move.opponent())
+    }
+
+    /// Converts a move between the frame of the side to move and the absolute frame.
+    fn orient(self, mv: Move) -> Move {
+        match self.to_move {
+            Color::White => mv,
+            Color::Black => Move {
+                from: State::<WIDTH, HEIGHT, B>::AREA - 1 - mv.from,
+                to: State::<WIDTH, HEIGHT, B>::AREA - 1 - mv.to,
+                ..mv
+            },
+        }
+    }
+
+    /// The legal moves, in absolute squares.
+    pub fn moves(self) -> impl Iterator<Item = Move> {
+        self.state.moves().map(move |mv| self.orient(mv))
+    }
+
+    /// Plays `mv`, given in absolute squares, which must be legal.
+    pub fn play(self, mv: Move) -> Self {
+        Self {
+            state: self.state.apply(self.orient(mv)),
+            to_move: self.to_move.opponent(),
+            ply: self.ply + 1,
+        }
+    }
+
+    pub fn children(self) -> impl Iterator<Item = Self> {
+        self.moves().map(move |mv| self.play(mv))
+    }
+
+    /// Counts the leaves under each move, in absolute squares.
+    pub fn perft_divide(self, depth: u32) -> Vec<(Move, u64)> {
+        let mut divide = self.state.perft_divide(depth);
+        for (mv, _) in &mut divide {
+            *mv = self.orient(*mv);
+        }
+        divide
+    }
+
+    /// Names `mv` as its squares separated by `-`, or by `x` for a capture, e.g. `b2xc3`.
+    pub fn move_name(self, mv: Move) -> String {
+        let separator = if mv.capture { 'x' } else { '-' };
+        format!(
+            "{}{separator}{}",
+            square_name::<WIDTH>(mv.from),
+            square_name::<WIDTH>(mv.to)
+        )
+    }
+
+    /// Finds the legal move named `name`. The separator is optional.
+    pub fn parse_move(self, name: &str) -> Option<Move> {
+        self.moves().find(|&mv| {
+            let from = square_name::<WIDTH>(mv.from);
+            let to = square_name::<WIDTH>(mv.to);
+            let separator = if mv.capture { "x" } else { "-" };
+            [format!("{from}{separator}{to}"), format!("{from}{to}")].contains(&name.to_string())
+        })
+    }
+}
+
+/// The name of `square`, e.g. `b3`, or `ab3` on boards wider than 26 files.
+pub fn square_name<const WIDTH: u32>(square: u32) -> String {
+    format!("{}{}", notation::file(square % WIDTH), square / WIDTH + 1)
+}
+
+/// Renders the board with White at the bottom, followed by the side to move.
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> fmt::Display for Position<WIDTH, HEIGHT, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.to_move {
+            Color::White => write!(f, "{}", self.state)?,
+            Color::Black => write!(f, "{:#}", self.state)?,
+        }
+        writeln!(f, "{} to move", self.to_move)
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> FromStr for Position<WIDTH, HEIGHT, B> {
+    type Err = ParseStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let state = s.parse()?;
+        let to_move = match s.split_whitespace().last() {
+            Some("b") => Color::Black,
+            _ => Color::White,
+        };
+        Ok(Self::new(state, to_move))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_orientation() {
+        let position = Position::<3, 5>::default();
+        let after = position.play(position.parse_move("a2-a3").unwrap());
+        assert_eq!(after.pieces(Color::White), 0b111_111 ^ 1 << 3 | 1 << 6);
+        assert_eq!(after.pieces(Color::Black), position.pieces(Color::Black));
+        assert_eq!(
+            after.to_string(),
+            "5 BBB\n4 BBB\n3 W..\n2 .WW\n1 WWW\n  abc\nBlack to move\n",
+        );
+
+        let names: Vec<_> = after.moves().map(|mv| after.move_name(mv)).collect();
+        assert_eq!(names.len(), 6);
+        assert!(names.iter().all(|name| name.contains('4')));
+        assert!(names.contains(&"b4xa3".to_string()));
+    }
+
+    #[test]
+    fn parse_matches_play() {
+        let position = Position::<3, 5>::default();
+        let position = position.play(position.parse_move("b2c3").unwrap());
+        let parsed: Position<3, 5> = "WWW/W.W/..W/BBB/BBB b".parse().unwrap();
+        assert_eq!(parsed.state(), position.state());
+        assert_eq!(parsed.to_move(), position.to_move());
+        assert_eq!(position.parse_move("b4-b3"), parsed.parse_move("b4b3"));
+    }
+
+    #[test]
+    fn wide_board_names() {
+        assert_eq!(square_name::<30>(25), "z1");
+        assert_eq!(square_name::<30>(26 + 30), "aa2");
+        let position = Position::<30, 4, u128>::default();
+        let mv = position.parse_move("ab2xac3").unwrap();
+        assert_eq!(position.move_name(mv), "ab2xac3");
+        assert_eq!(position.parse_move("ab2ac3"), Some(mv));
+        assert_eq!(position.parse_move("a2-a3"), position.parse_move("a2a3"));
+    }
+
+    #[test]
+    fn game_ends() {
+        let mut position = Position::<2, 4>::default();
+        while position.winner().is_none() {
+            position = position.children().next().unwrap();
+        }
+        assert_eq!(position.winner(), Some(position.to_move().opponent()));
+    }
+}
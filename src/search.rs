@@ -0,0 +1,471 @@
+//! A game-playing engine for boards too large to solve.
+//!
+//! [`Searcher`] runs a negamax alpha-beta search, deepened iteratively until it runs out of depth,
+//! nodes or time. Moves are ordered by the transposition table's best move, then wins and
+//! captures, then killer moves and the history heuristic. Scores are from the side to move's
+//! perspective, with wins and losses in `n` plies scored `WIN - n` and `n - WIN`. Leaves are scored
+//! by an [`Evaluator`], [`Weights::default`] unless another is given.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{search::{Limits, Searcher}, solver::Outcome, State};
+//! let mut searcher = Searcher::new();
+//! let limits = Limits { depth: 14, ..Limits::default() };
+//! let result = searcher.search(State::<3, 4>::default(), limits);
+//! assert_eq!(result.outcome(), Some(Outcome::loss(12)));
+//! ```
+
+use crate::{
+    eval::{Evaluator, Weights},
+    hash::table_len,
+    solver::Outcome,
+    Bitboard, Move, State,
+};
+use std::time::{Duration, Instant};
+
+/// The score of winning immediately.
+pub const WIN: i32 = 1_000_000;
+/// The deepest the search goes. Every move advances one of at most `2 * WIDTH` pieces a row, so a
+/// game lasts fewer than `4 * AREA` plies, which is at most this on boards of up to 128 squares.
+const MAX_PLY: usize = 4 * 128;
+/// Scores this close to `WIN` are wins or losses.
+const DECIDED: i32 = WIN - MAX_PLY as i32;
+
+/// When to stop searching. The search always completes depth 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub depth: u32,
+    pub nodes: u64,
+    pub time: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            depth: MAX_PLY as u32 - 1,
+            nodes: u64::MAX,
+            time: None,
+        }
+    }
+}
+
+/// The result of the deepest completed iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The best move, or `None` if the game is over.
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    /// The number of nodes searched over all iterations.
+    pub nodes: u64,
+    /// The principal variation, starting with the best move.
+    pub pv: Vec<Move>,
+}
+
+impl SearchResult {
+    /// The outcome, if the score proves a win or loss.
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.score >= DECIDED {
+            Some(Outcome::win((WIN - self.score) as u32))
+        } else if self.score <= -DECIDED {
+            Some(Outcome::loss((WIN + self.score) as u32))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    hash: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// Converts a score relative to the root into one relative to the node at `ply`, so that decided
+/// scores can be stored in the table independently of where the position was found.
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    match score {
+        s if s >= DECIDED => s + ply as i32,
+        s if s <= -DECIDED => s - ply as i32,
+        s => s,
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    match score {
+        s if s >= DECIDED => s - ply as i32,
+        s if s <= -DECIDED => s + ply as i32,
+        s => s,
+    }
+}
+
+/// An alpha-beta searcher, keeping its transposition table and move ordering statistics across
+/// searches.
+#[derive(Debug, Clone)]
+pub struct Searcher<const WIDTH: u32, const HEIGHT: u32, B = u64, E = Weights> {
+    evaluator: E,
+    table: Vec<Option<Entry>>,
+    /// Two quiet moves per ply that recently caused a cutoff.
+    killers: Vec<[Option<Move>; 2]>,
+    /// Cutoff counts of quiet moves, by origin and destination.
+    history: Vec<u32>,
+    /// The principal variation from each ply.
+    pv: Vec<Vec<Move>>,
+    nodes: u64,
+    limits: Limits,
+    start: Instant,
+    stopped: bool,
+    bitboard: std::marker::PhantomData<B>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Default for Searcher<WIDTH, HEIGHT, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Searcher<WIDTH, HEIGHT, B> {
+    /// Creates a searcher with a transposition table of about 16 MiB.
+    pub fn new() -> Self {
+        Self::with_megabytes(16)
+    }
+
+    /// Creates a searcher with a transposition table of about `megabytes` MiB.
+    pub fn with_megabytes(megabytes: usize) -> Self {
+        Self::with_evaluator(Weights::default(), megabytes)
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard, E: Evaluator<WIDTH, HEIGHT, B>>
+    Searcher<WIDTH, HEIGHT, B, E>
+{
+    /// Creates a searcher scoring leaves with `evaluator`, with a transposition table of about
+    /// `megabytes` MiB.
+    pub fn with_evaluator(evaluator: E, megabytes: usize) -> Self {
+        let area = State::<WIDTH, HEIGHT, B>::AREA as usize;
+        Self {
+            evaluator,
+            table: vec![None; table_len::<Option<Entry>>(megabytes)],
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![0; area * area],
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            nodes: 0,
+            limits: Limits::default(),
+            start: Instant::now(),
+            stopped: false,
+            bitboard: std::marker::PhantomData,
+        }
+    }
+
+    /// Forgets everything learned in previous searches.
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+        self.killers.fill([None; 2]);
+        self.history.fill(0);
+    }
+
+    /// Searches `state` by iterative deepening within `limits`.
+    pub fn search(&mut self, state: State<WIDTH, HEIGHT, B>, limits: Limits) -> SearchResult {
+        self.limits = limits;
+        debug_assert!(4 * State::<WIDTH, HEIGHT, B>::AREA as usize <= MAX_PLY);
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.stopped = false;
+        self.killers.fill([None; 2]);
+        for count in &mut self.history {
+            *count /= 2;
+        }
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        for depth in 1..=limits.depth.clamp(1, MAX_PLY as u32 - 1) {
+            let score = self.negamax(state, depth, 0, -WIN, WIN);
+            if self.stopped {
+                break;
+            }
+            result = SearchResult {
+                best_move: self.pv[0].first().copied(),
+                score,
+                depth,
+                nodes: self.nodes,
+                pv: self.pv[0].clone(),
+            };
+            // A decided score within the horizon is exact, and won't change with depth.
+            if score.abs() >= WIN - depth as i32 {
+                break;
+            }
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    /// Whether the budget is spent. Depth 1 always completes, so there is a move to play.
+    fn out_of_budget(&self, depth: u32) -> bool {
+        depth > 1
+            && (self.nodes >= self.limits.nodes
+                || self
+                    .limits
+                    .time
+                    .is_some_and(|time| self.start.elapsed() >= time))
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        hash as usize & self.table.len() - 1
+    }
+
+    fn negamax(
+        &mut self,
+        state: State<WIDTH, HEIGHT, B>,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        self.pv[ply].clear();
+        if state.is_lost() {
+            return ply as i32 - WIN;
+        }
+        if self.nodes & 1023 == 0 && self.out_of_budget(depth + ply as u32) {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return 0;
+        }
+
+        let mut moves: Vec<_> = state.moves().collect();
+        if moves.is_empty() {
+            return ply as i32 - WIN;
+        }
+        if let Some(win) = state.immediate_win_moves().next() {
+            self.pv[ply].push(win);
+            return WIN - ply as i32 - 1;
+        }
+        if depth == 0 || ply == MAX_PLY - 1 {
+            return self
+                .evaluator
+                .evaluate(state)
+                .clamp(1 - DECIDED, DECIDED - 1);
+        }
+
+        let hash = state.hash64();
+        let slot = self.slot(hash);
+        let mut table_move = None;
+        if let Some(entry) = self.table[slot].filter(|entry| entry.hash == hash) {
+            table_move = entry.best_move;
+            let score = score_from_table(entry.score, ply);
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if ply > 0 && entry.depth >= depth && cutoff {
+                return score;
+            }
+        }
+
+        let area = State::<WIDTH, HEIGHT, B>::AREA as usize;
+        let killers = self.killers[ply];
+        let history = &self.history;
+        moves.sort_by_cached_key(|&mv| {
+            let priority = if Some(mv) == table_move {
+                u32::MAX
+            } else if mv.capture {
+                u32::MAX - 1
+            } else if killers.contains(&Some(mv)) {
+                u32::MAX - 2
+            } else {
+                history[mv.from as usize * area + mv.to as usize].min(u32::MAX - 3)
+            };
+            std::cmp::Reverse(priority)
+        });
+
+        let original_alpha = alpha;
+        let mut best = -WIN;
+        let mut best_move = None;
+        for mv in moves {
+            let score = -self.negamax(state.apply(mv), depth - 1, ply + 1, -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+                let (head, tail) = self.pv.split_at_mut(ply + 1);
+                head[ply].clear();
+                head[ply].push(mv);
+                head[ply].extend_from_slice(&tail[0]);
+            }
+            if alpha >= beta {
+                if !mv.capture {
+                    let killers = &mut self.killers[ply];
+                    if killers[0] != Some(mv) {
+                        killers[1] = killers[0];
+                        killers[0] = Some(mv);
+                    }
+                    let count = &mut self.history[mv.from as usize * area + mv.to as usize];
+                    *count = count.saturating_add(depth * depth);
+                }
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table[slot] = Some(Entry {
+            hash,
+            depth,
+            score: score_to_table(best, ply),
+            bound,
+            best_move,
+        });
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{exact::Solver, verify};
+
+    fn limits(depth: u32) -> Limits {
+        Limits {
+            depth,
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn immediate_win() {
+        let state: State<4, 5> = "4/WW2/4/.B.W/BB2 w".parse().unwrap();
+        let result = Searcher::new().search(state, limits(5));
+        assert_eq!(result.outcome(), Some(Outcome::win(1)));
+        assert_eq!(result.pv, [result.best_move.unwrap()]);
+        assert!(state.apply(result.best_move.unwrap()).is_lost());
+    }
+
+    #[test]
+    fn game_over() {
+        let state: State<4, 5> = "4/4/4/4/4 w".parse().unwrap();
+        let result = Searcher::new().search(state, limits(5));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.outcome(), Some(Outcome::loss(0)));
+    }
+
+    #[test]
+    fn matches_solver() {
+        let mut searcher = Searcher::with_megabytes(1);
+        let mut solver = Solver::new();
+        for state in verify::legal_states::<3, 4>().step_by(1009) {
+            let result = searcher.search(state, limits(24));
+            assert_eq!(result.outcome(), Some(solver.solve(state)), "{state:?}");
+        }
+    }
+
+    #[test]
+    fn principal_variation() {
+        let state = State::<3, 5>::default();
+        let result = Searcher::new().search(state, limits(20));
+        assert_eq!(result.outcome(), Some(crate::solver::solve(state)));
+        assert_eq!(result.pv.first(), result.best_move.as_ref());
+
+        // The principal variation is a legal line, though table cutoffs may cut it short.
+        let mut position = state;
+        for &mv in &result.pv {
+            assert!(position.moves().any(|legal| legal == mv));
+            position = position.apply(mv);
+        }
+        assert!(result.pv.len() as u32 <= result.outcome().unwrap().plies);
+    }
+
+    #[test]
+    fn custom_evaluator() {
+        struct Extreme;
+        impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Evaluator<WIDTH, HEIGHT, B> for Extreme {
+            fn evaluate(&self, _: State<WIDTH, HEIGHT, B>) -> i32 {
+                i32::MAX
+            }
+        }
+
+        // Heuristic scores are clamped, so they are never mistaken for wins or losses.
+        let mut searcher = Searcher::with_evaluator(Extreme, 1);
+        let result = searcher.search(State::<6, 6>::default(), limits(1));
+        assert_eq!(result.score, 1 - DECIDED);
+        assert_eq!(result.outcome(), None);
+        let result = searcher.search(State::<6, 6>::default(), limits(2));
+        assert_eq!(result.score, DECIDED - 1);
+    }
+
+    #[test]
+    fn deep_plies() {
+        // Games on the largest boards run past 256 plies, so nodes that deep must be searchable.
+        let state: State<16, 8, u128> = "16/16/16/16/16/3W12/16/15B w".parse().unwrap();
+        for ply in [300, MAX_PLY - 4] {
+            let mut searcher = Searcher::with_megabytes(1);
+            let score = searcher.negamax(state, 3, ply, -WIN, WIN);
+            assert_eq!(score, WIN - ply as i32 - 3);
+            assert!(score >= DECIDED);
+            assert_eq!(searcher.pv[ply].len(), 3);
+        }
+
+        // The deepest node is left to the evaluator.
+        let mut searcher = Searcher::with_megabytes(1);
+        let score = searcher.negamax(state, 3, MAX_PLY - 1, -WIN, WIN);
+        assert!(score.abs() < DECIDED);
+    }
+
+    #[test]
+    fn budgets() {
+        let state = State::<8, 8>::default();
+        let mut searcher = Searcher::with_megabytes(1);
+        let result = searcher.search(
+            state,
+            Limits {
+                nodes: 5000,
+                ..Limits::default()
+            },
+        );
+        assert!(result.best_move.is_some());
+        assert!(result.nodes < 5000 + 1024, "{} nodes", result.nodes);
+
+        let start = Instant::now();
+        let result = searcher.search(
+            state,
+            Limits {
+                time: Some(Duration::from_millis(50)),
+                ..Limits::default()
+            },
+        );
+        assert!(result.best_move.is_some());
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Even a depth limit of 0 completes depth 1.
+        let result = searcher.search(state, limits(0));
+        assert_eq!(result.depth, 1);
+        assert!(state.moves().any(|mv| Some(mv) == result.best_move));
+    }
+}
@@ -0,0 +1,431 @@
+//! Breakthrough predicates in algebraic normal form.
+//!
+//! A `State<WIDTH, HEIGHT>` is encoded as 128 Boolean variables: variable `i` is bit `i` of
+//! `me`, and variable `64 + i` is bit `i` of `them`. Moves are applied to formulas by
+//! substitution, so "some move leads to a position satisfying `f`" is computed without ever
+//! enumerating positions.
+//!
+//! All formulas are reduced modulo the constraints satisfied by legal positions (see
+//! [`evaluate`]), so two formulas agreeing on every legal position are equal.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{solver::anf, State};
+//! let win_in_1 = anf::win_within::<3, 4>(1);
+//! assert!(!anf::evaluate(&win_in_1, State::<3, 4>::default()));
+//!
+//! // Custom predicates are composed from the same building blocks.
+//! let has_capture = anf::transitions::<3, 4>()
+//!     .filter(|t| t.is_diagonal())
+//!     .fold(anf::Formula::zero(), |acc, t| acc.or(&t.guard().and(&anf::them(t.to()))));
+//! // On a 3x4 board both armies start in contact.
+//! assert!(anf::evaluate(&has_capture.to_anf(), State::<3, 4>::default()));
+//! ```
+
+use crate::State;
+use normform::{Anf, BitTerm128 as Term};
+use std::collections::HashSet;
+
+const THEM: u32 = u64::BITS;
+
+/// Returns the formula for "the side to move has a piece on `square`".
+pub fn me<const WIDTH: u32, const HEIGHT: u32>(square: u32) -> Formula<WIDTH, HEIGHT> {
+    Formula::var(square)
+}
+
+/// Returns the formula for "the opponent has a piece on `square`".
+pub fn them<const WIDTH: u32, const HEIGHT: u32>(square: u32) -> Formula<WIDTH, HEIGHT> {
+    Formula::var(THEM + square)
+}
+
+/// A formula reduced modulo the constraints satisfied by every legal position: no square holds
+/// both sides' pieces, and the side to move has no piece on the last row. Monomials violating
+/// either are identically zero on legal positions and are dropped eagerly, which keeps the
+/// formulas orders of magnitude smaller than their unreduced counterparts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formula<const WIDTH: u32, const HEIGHT: u32> {
+    terms: HashSet<u128>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32> Formula<WIDTH, HEIGHT> {
+    const LAST_ROW: u128 =
+        (State::<WIDTH, HEIGHT>::ROW_MASK as u128) << State::<WIDTH, HEIGHT>::AREA - WIDTH;
+
+    pub fn zero() -> Self {
+        Self {
+            terms: HashSet::new(),
+        }
+    }
+
+    pub fn one() -> Self {
+        Self::zero().with(0)
+    }
+
+    fn var(i: u32) -> Self {
+        Self::zero().with(1 << i)
+    }
+
+    fn with(mut self, term: u128) -> Self {
+        self.toggle(term);
+        self
+    }
+
+    fn toggle(&mut self, term: u128) {
+        let me = term as u64 as u128;
+        let them = term >> THEM;
+        if me & (them | Self::LAST_ROW) != 0 {
+            return;
+        }
+        if !self.terms.remove(&term) {
+            self.terms.insert(term);
+        }
+    }
+
+    /// Reduces an arbitrary formula.
+    pub fn from_anf(anf: &Anf<Term>) -> Self {
+        anf.iter()
+            .fold(Self::zero(), |acc, &term| acc.with(term.into()))
+    }
+
+    pub fn to_anf(&self) -> Anf<Term> {
+        self.terms
+            .iter()
+            .fold(Anf::zero(), |acc, &term| acc ^ Anf::from(Term::from(term)))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The number of monomials.
+    pub fn term_count(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// Evaluates the formula over the variables of `state`, which must be legal.
+    ///
+    /// Unlike [`evaluate`], this only looks up the monomials made of variables set in `state`
+    /// when there are fewer of those than monomials in the formula.
+    pub fn evaluate(&self, state: State<WIDTH, HEIGHT>) -> bool {
+        let assignment = state.me as u128 | (state.them as u128) << THEM;
+        let subsets = 1usize.checked_shl(assignment.count_ones());
+        if subsets.is_some_and(|subsets| subsets < self.terms.len()) {
+            let mut subset = assignment;
+            let mut parity = false;
+            loop {
+                parity ^= self.terms.contains(&subset);
+                if subset == 0 {
+                    break parity;
+                }
+                subset = subset - 1 & assignment;
+            }
+        } else {
+            self.terms
+                .iter()
+                .filter(|&&term| term & !assignment == 0)
+                .count()
+                % 2
+                == 1
+        }
+    }
+
+    pub fn xor(mut self, rhs: &Self) -> Self {
+        for &term in &rhs.terms {
+            self.toggle(term);
+        }
+        self
+    }
+
+    pub fn and(&self, rhs: &Self) -> Self {
+        let mut out = Self::zero();
+        for &a in &self.terms {
+            for &b in &rhs.terms {
+                out.toggle(a | b);
+            }
+        }
+        out
+    }
+
+    pub fn or(self, rhs: &Self) -> Self {
+        let both = self.and(rhs);
+        self.xor(rhs).xor(&both)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        self.with(0)
+    }
+}
+
+/// One branch of the transition relation: a single move of the side to move, described in its
+/// own frame.
+///
+/// The relation between the variables of a position and those of its child is the disjunction
+/// of all branches, each of which is a [`guard`](Self::guard) over the current variables together
+/// with a substitution giving every [`next`](Self::next) variable as a function of the current
+/// ones. Writing the relation out as a single formula over both sets of variables would need
+/// `4 * AREA` variables and exponentially many monomials, so it is only ever used through
+/// [`preimage`](Self::preimage).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition<const WIDTH: u32, const HEIGHT: u32> {
+    from: u32,
+    to: u32,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32> Transition<WIDTH, HEIGHT> {
+    const FLIP: u32 = u64::BITS - State::<WIDTH, HEIGHT>::AREA;
+
+    /// The square the piece moves from.
+    pub fn from(self) -> u32 {
+        self.from
+    }
+
+    /// The square the piece moves to.
+    pub fn to(self) -> u32 {
+        self.to
+    }
+
+    /// Whether the move is diagonal, and may thus be a capture.
+    pub fn is_diagonal(self) -> bool {
+        self.to != self.from + WIDTH
+    }
+
+    /// The formula for "this move is legal".
+    pub fn guard(self) -> Formula<WIDTH, HEIGHT> {
+        let free = me(self.to).not();
+        let free = if self.is_diagonal() {
+            free
+        } else {
+            free.and(&them(self.to).not())
+        };
+        me(self.from).and(&free)
+    }
+
+    /// Returns variable `var` of the child as a formula over the current variables, assuming the
+    /// move is legal. The child is seen from the opponent's perspective, so its `me` variables
+    /// come from the current `them` variables and vice versa, mirrored.
+    pub fn next(self, var: u32) -> Formula<WIDTH, HEIGHT> {
+        let square = State::<WIDTH, HEIGHT>::AREA - 1 - var % THEM;
+        if var < THEM {
+            if square == self.to {
+                Formula::zero()
+            } else {
+                them(square)
+            }
+        } else if square == self.from {
+            Formula::zero()
+        } else if square == self.to {
+            Formula::one()
+        } else {
+            me(square)
+        }
+    }
+
+    /// Returns `f(child)` as a formula over the current variables, conjoined with the
+    /// [`guard`](Self::guard).
+    pub fn preimage(self, f: &Formula<WIDTH, HEIGHT>) -> Formula<WIDTH, HEIGHT> {
+        let from = 1u128 << self.from;
+        let to_me = 1u128 << self.to;
+        let to_them = to_me << THEM;
+
+        let guards: &[u128] = if self.is_diagonal() {
+            &[0, to_me]
+        } else {
+            &[0, to_me, to_them, to_me | to_them]
+        };
+
+        let mut out = Formula::zero();
+        for &term in &f.terms {
+            let me = (term >> THEM) as u64;
+            let them = term as u64;
+            let renamed = ((me.reverse_bits() >> Self::FLIP) as u128)
+                | ((them.reverse_bits() >> Self::FLIP) as u128) << THEM;
+
+            // After the move, `from` is vacated and `to` holds our piece and none of theirs.
+            if renamed & (from | to_them) != 0 {
+                continue;
+            }
+            let renamed = renamed & !to_me | from;
+            for &guard in guards {
+                out.toggle(renamed | guard);
+            }
+        }
+        out
+    }
+}
+
+/// Returns every branch of the transition relation.
+pub fn transitions<const WIDTH: u32, const HEIGHT: u32>(
+) -> impl Iterator<Item = Transition<WIDTH, HEIGHT>> {
+    (0..State::<WIDTH, HEIGHT>::AREA - WIDTH).flat_map(|from| {
+        let file = from % WIDTH;
+        let to = from + WIDTH;
+        [
+            (file != 0).then_some(to - 1),
+            Some(to),
+            (file != WIDTH - 1).then_some(to + 1),
+        ]
+        .into_iter()
+        .flatten()
+        .map(move |to| Transition { from, to })
+    })
+}
+
+/// The formula for `State::is_lost`.
+pub fn is_lost<const WIDTH: u32, const HEIGHT: u32>() -> Formula<WIDTH, HEIGHT> {
+    (0..WIDTH)
+        .fold(Formula::one(), |acc, i| acc.and(&them(i).not()))
+        .not()
+}
+
+/// The formula for "some legal move leads to a position satisfying `f`".
+pub fn exists_move<const WIDTH: u32, const HEIGHT: u32>(
+    f: &Formula<WIDTH, HEIGHT>,
+) -> Formula<WIDTH, HEIGHT> {
+    transitions().fold(Formula::zero(), |acc, t| acc.or(&t.preimage(f)))
+}
+
+/// Computes the formulas for "the side to move wins within `k` plies" and "the side to move
+/// loses within `k` plies". A side without legal moves has lost.
+fn within<const WIDTH: u32, const HEIGHT: u32>(
+    k: u32,
+) -> (Formula<WIDTH, HEIGHT>, Formula<WIDTH, HEIGHT>) {
+    let lost = is_lost::<WIDTH, HEIGHT>();
+    let stuck = lost.clone().or(&exists_move(&Formula::one()).not());
+    (0..k).fold((Formula::zero(), stuck), |(win, loss), _| {
+        (
+            lost.clone().not().and(&exists_move(&loss)),
+            lost.clone().or(&exists_move(&win.not()).not()),
+        )
+    })
+}
+
+/// Returns the formula for "the side to move wins within `k` plies".
+///
+/// The formula is only meaningful for legal positions, see [`evaluate`].
+pub fn win_within<const WIDTH: u32, const HEIGHT: u32>(k: u32) -> Anf<Term> {
+    within::<WIDTH, HEIGHT>(k).0.to_anf()
+}
+
+/// Evaluates a formula over the variables of `state`.
+///
+/// `state` must be legal: no square may hold pieces of both sides, and the side to move must not
+/// have a piece on the last row.
+pub fn evaluate<const WIDTH: u32, const HEIGHT: u32>(
+    anf: &Anf<Term>,
+    state: State<WIDTH, HEIGHT>,
+) -> bool {
+    let assignment = state.me as u128 | (state.them as u128) << THEM;
+    anf.iter()
+        .filter(|&&term| u128::from(term) & !assignment == 0)
+        .count()
+        % 2
+        == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::verify::{legal_states, wins_within};
+
+    #[test]
+    fn default_position() {
+        for k in 0..3 {
+            let state = State::<3, 4>::default();
+            assert_eq!(
+                evaluate(&win_within::<3, 4>(k), state),
+                wins_within(state, k)
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_many_variables() {
+        // All 64 squares are taken, too many variables to enumerate the subsets of.
+        assert!(Formula::<16, 4>::one().evaluate(State::default()));
+        assert!(!Formula::<16, 4>::zero().evaluate(State::default()));
+    }
+
+    #[test]
+    fn game_line() {
+        let win = win_within::<3, 4>(2);
+        let mut state = State::<3, 4>::default();
+        while !state.is_lost() {
+            assert_eq!(evaluate(&win, state), wins_within(state, 2));
+            let Some(next) = state.children().next() else {
+                break;
+            };
+            state = next;
+        }
+    }
+
+    #[test]
+    fn is_lost_matches() {
+        let lost = is_lost::<3, 4>().to_anf();
+        for state in legal_states::<3, 4>() {
+            assert_eq!(evaluate(&lost, state), state.is_lost(), "{state:?}");
+        }
+    }
+
+    #[test]
+    fn has_move_matches() {
+        let has_move = exists_move::<3, 4>(&Formula::one()).to_anf();
+        for state in legal_states::<3, 4>() {
+            let expected = state.children().next().is_some();
+            assert_eq!(evaluate(&has_move, state), expected, "{state:?}");
+        }
+    }
+
+    #[test]
+    fn has_capture_matches() {
+        let has_capture = transitions::<3, 4>()
+            .filter(|t| t.is_diagonal())
+            .fold(Formula::zero(), |acc, t| {
+                acc.or(&t.guard().and(&them(t.to())))
+            })
+            .to_anf();
+        for state in legal_states::<3, 4>() {
+            let pieces = state.them.count_ones();
+            let expected = state.children().any(|child| child.me.count_ones() < pieces);
+            assert_eq!(evaluate(&has_capture, state), expected, "{state:?}");
+        }
+    }
+
+    #[test]
+    fn transitions_match_children() {
+        let branches: Vec<_> = transitions::<3, 4>()
+            .map(|t| {
+                let next: Vec<_> = (0..12)
+                    .chain(THEM..THEM + 12)
+                    .map(|var| (var, t.next(var).to_anf()))
+                    .collect();
+                (t.guard().to_anf(), next)
+            })
+            .collect();
+
+        for state in legal_states::<3, 4>().filter(|state| !state.is_lost()) {
+            let mut expected: Vec<_> = state.children().collect();
+            let mut actual: Vec<_> = branches
+                .iter()
+                .filter(|(guard, _)| evaluate(guard, state))
+                .map(|(_, next)| {
+                    let mut child = State { me: 0, them: 0 };
+                    for (var, f) in next {
+                        if evaluate(f, state) {
+                            if *var < THEM {
+                                child.me |= 1 << var;
+                            } else {
+                                child.them |= 1 << var - THEM;
+                            }
+                        }
+                    }
+                    child
+                })
+                .collect();
+            expected.sort_by_key(|child| (child.me, child.them));
+            actual.sort_by_key(|child| (child.me, child.them));
+            assert_eq!(actual, expected, "{state:?}");
+        }
+    }
+}
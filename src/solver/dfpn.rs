@@ -0,0 +1,313 @@
+//! Depth-first proof-number search.
+//!
+//! df-pn explores the same most-proving nodes as [PN search](super::pn), but depth-first: each
+//! node is searched until its proof or disproof number reaches a threshold set by its parent, and
+//! the numbers of nodes left behind are kept in a transposition table instead of a tree. The table
+//! has a fixed size, so memory use is bounded, at the cost of searching again whatever gets
+//! overwritten.
+//!
+//! In games with repetitions, a table keyed on positions alone suffers from the graph history
+//! interaction problem: a position's value can depend on the path to it. Breakthrough has no
+//! repetitions, as every move advances a piece, so a position's value is a function of the
+//! position. What does depend on the path is the number of plies left when proving a win within a
+//! bound, so bounded searches include it, and the roles of the players, in the key.
+//!
+//! [`Solver::winner`] proves who wins. [`Solver::solve`] then finds how fast, by proving wins within
+//! increasing bounds, so that its result is the same [`Outcome`] as that of the other solvers.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{solver::{dfpn, Outcome, Player}, State};
+//! let mut solver = dfpn::Solver::new();
+//! assert_eq!(solver.winner(State::<4, 4>::default()), Player::Opponent);
+//! assert_eq!(dfpn::solve(State::<3, 4>::default()), Outcome::loss(12));
+//! ```
+
+use super::{Outcome, Player};
+use crate::{
+    hash::{mix, table_len},
+    Bitboard, State,
+};
+
+const INFINITY: u64 = u64::MAX / 4;
+
+/// What is being proven: that the side to move wins, or, with a bound, whether the root's side
+/// wins within that many plies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    Win,
+    /// A win within `plies` plies for the prover, and whether the side to move is the prover.
+    Within {
+        plies: u32,
+        prover: bool,
+    },
+}
+
+impl Goal {
+    fn child(self) -> Self {
+        match self {
+            Self::Win => Self::Win,
+            Self::Within { plies, prover } => Self::Within {
+                plies: plies - 1,
+                prover: !prover,
+            },
+        }
+    }
+
+    fn key(self) -> u64 {
+        match self {
+            Self::Win => 0,
+            Self::Within { plies, prover } => mix(plies as u64 * 2 + prover as u64 + 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry<const WIDTH: u32, const HEIGHT: u32, B> {
+    state: State<WIDTH, HEIGHT, B>,
+    goal: Goal,
+    /// The proof and disproof numbers, for the side to move reaching its goal: the prover winning
+    /// in time, or the defender holding out.
+    proof: u64,
+    disproof: u64,
+}
+
+/// A df-pn solver, keeping its transposition table across searches.
+#[derive(Debug, Clone)]
+pub struct Solver<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    table: Vec<Option<Entry<WIDTH, HEIGHT, B>>>,
+    nodes: u64,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Default for Solver<WIDTH, HEIGHT, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Solver<WIDTH, HEIGHT, B> {
+    /// Creates a solver with a transposition table of about 64 MiB.
+    pub fn new() -> Self {
+        Self::with_megabytes(64)
+    }
+
+    /// Creates a solver with a transposition table of about `megabytes` MiB.
+    pub fn with_megabytes(megabytes: usize) -> Self {
+        Self {
+            table: vec![None; table_len::<Option<Entry<WIDTH, HEIGHT, B>>>(megabytes)],
+            nodes: 0,
+        }
+    }
+
+    /// The number of nodes searched so far.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Proves who wins `state`.
+    pub fn winner(&mut self, state: State<WIDTH, HEIGHT, B>) -> Player {
+        let (proof, _) = self.search(state, Goal::Win, INFINITY, INFINITY);
+        if proof == 0 {
+            Player::Mover
+        } else {
+            Player::Opponent
+        }
+    }
+
+    /// Solves `state`, with the winner playing the fastest win and the loser the slowest loss.
+    pub fn solve(&mut self, state: State<WIDTH, HEIGHT, B>) -> Outcome {
+        let prover = self.winner(state) == Player::Mover;
+        // Wins take an odd number of plies, and losses an even number.
+        let mut plies = !prover as u32 ^ 1;
+        loop {
+            let goal = Goal::Within { plies, prover };
+            let (proof, disproof) = self.search(state, goal, INFINITY, INFINITY);
+            match (prover, proof, disproof) {
+                (true, 0, _) => return Outcome::win(plies),
+                (false, _, 0) => return Outcome::loss(plies),
+                _ => plies += 2,
+            }
+        }
+    }
+
+    fn slot(&self, state: State<WIDTH, HEIGHT, B>, goal: Goal) -> usize {
+        (state.hash64() ^ goal.key()) as usize & self.table.len() - 1
+    }
+
+    /// The numbers of `state` from the table, or estimates for a new node.
+    fn lookup(&self, state: State<WIDTH, HEIGHT, B>, goal: Goal) -> (u64, u64) {
+        if state.is_lost() {
+            return (INFINITY, 0);
+        }
+        let key = state.canonical();
+        match self.table[self.slot(key, goal)] {
+            Some(entry) if entry.state == key && entry.goal == goal => {
+                (entry.proof, entry.disproof)
+            }
+            _ => (1, 1),
+        }
+    }
+
+    fn store(&mut self, state: State<WIDTH, HEIGHT, B>, goal: Goal, proof: u64, disproof: u64) {
+        let key = state.canonical();
+        let slot = self.slot(key, goal);
+        self.table[slot] = Some(Entry {
+            state: key,
+            goal,
+            proof,
+            disproof,
+        });
+    }
+
+    /// Searches `state` until its proof number reaches `max_proof` or its disproof number reaches
+    /// `max_disproof`, returning both.
+    fn search(
+        &mut self,
+        state: State<WIDTH, HEIGHT, B>,
+        goal: Goal,
+        max_proof: u64,
+        max_disproof: u64,
+    ) -> (u64, u64) {
+        self.nodes += 1;
+        let children: Vec<_> = state.children().collect();
+        // A side without moves has lost. Out of time, the prover has failed and the defender has
+        // held out. Otherwise a side that can win right away has won.
+        let numbers = if state.is_lost() || children.is_empty() {
+            Some((INFINITY, 0))
+        } else if let Goal::Within { plies: 0, prover } = goal {
+            Some(if prover { (INFINITY, 0) } else { (0, INFINITY) })
+        } else if state.immediate_win_moves().next().is_some() {
+            Some((0, INFINITY))
+        } else {
+            None
+        };
+        if let Some((proof, disproof)) = numbers {
+            self.store(state, goal, proof, disproof);
+            return (proof, disproof);
+        }
+
+        // The children's numbers, updated from their searches rather than the table, so that
+        // progress isn't lost when entries are overwritten.
+        let child_goal = goal.child();
+        let mut numbers: Vec<_> = children
+            .iter()
+            .map(|&child| self.lookup(child, child_goal))
+            .collect();
+        loop {
+            // The side to move reaches its goal if some child fails to reach its own.
+            let mut proof = INFINITY;
+            let mut second = INFINITY;
+            let mut best = 0;
+            for (i, &(_, child_disproof)) in numbers.iter().enumerate() {
+                if child_disproof < proof {
+                    second = proof;
+                    proof = child_disproof;
+                    best = i;
+                } else if child_disproof < second {
+                    second = child_disproof;
+                }
+            }
+            let disproof = if numbers
+                .iter()
+                .any(|&(child_proof, _)| child_proof >= INFINITY)
+            {
+                INFINITY
+            } else {
+                let sum = numbers.iter().fold(0, |sum: u64, &(child_proof, _)| {
+                    sum.saturating_add(child_proof)
+                });
+                sum.min(INFINITY - 1)
+            };
+
+            if proof >= max_proof || disproof >= max_disproof {
+                self.store(state, goal, proof, disproof);
+                return (proof, disproof);
+            }
+
+            let child_max_proof = (max_disproof - disproof).saturating_add(numbers[best].0);
+            let child_max_disproof = max_proof.min(second.saturating_add(1));
+            numbers[best] = self.search(
+                children[best],
+                child_goal,
+                child_max_proof.min(INFINITY),
+                child_max_disproof,
+            );
+        }
+    }
+}
+
+/// Solves `state` from scratch. See [`Solver::solve`].
+pub fn solve<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+    state: State<WIDTH, HEIGHT, B>,
+) -> Outcome {
+    Solver::new().solve(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{exact, pn, verify};
+
+    #[test]
+    fn matches_exact_solver() {
+        let mut solver = Solver::with_megabytes(16);
+        assert_eq!(solver.solve(State::<3, 4>::default()), Outcome::loss(12));
+        let mut exact = exact::Solver::new();
+        for state in verify::legal_states::<3, 4>().step_by(499) {
+            assert_eq!(solver.solve(state), exact.solve(state), "{state:?}");
+        }
+        assert_eq!(solve(State::<2, 5>::default()), Outcome::loss(10));
+    }
+
+    #[test]
+    fn matches_pn() {
+        let pn = pn::Solver::new(pn::Variant::Pn2, 16);
+        let mut solver = Solver::with_megabytes(16);
+        for state in verify::legal_states::<3, 4>().step_by(997) {
+            assert_eq!(
+                solver.winner(state),
+                pn.solve(state).unwrap().winner,
+                "{state:?}"
+            );
+        }
+        let state = State::<3, 5>::default();
+        assert_eq!(
+            Solver::with_megabytes(16).winner(state),
+            pn::Solver::new(pn::Variant::Pn2, 16)
+                .solve(state)
+                .unwrap()
+                .winner
+        );
+    }
+
+    #[test]
+    fn tiny_table() {
+        // Overwritten entries are searched again, which is slower but still correct.
+        let mut solver = Solver::with_megabytes(0);
+        assert_eq!(solver.table.len(), 1);
+        assert_eq!(solver.solve(State::<2, 5>::default()), Outcome::loss(10));
+        let mut solver = Solver::<3, 4>::with_megabytes(0);
+        solver.table = vec![None; 64];
+        assert_eq!(solver.solve(State::default()), Outcome::loss(12));
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn medium_boards() {
+        assert_eq!(solve(State::<4, 4>::default()), Outcome::loss(14));
+        assert_eq!(solve(State::<3, 5>::default()), Outcome::loss(18));
+        assert_eq!(solve(State::<5, 4>::default()), Outcome::loss(16));
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn large_boards() {
+        // Minutes each with a 2 GiB table. Proving the distances within increasing bounds takes
+        // much longer still.
+        let mut solver = Solver::<4, 6>::with_megabytes(2048);
+        assert_eq!(solver.winner(State::default()), Player::Mover);
+        let mut solver = Solver::<3, 8>::with_megabytes(2048);
+        assert_eq!(solver.winner(State::default()), Player::Mover);
+    }
+}
@@ -0,0 +1,140 @@
+//! Exact solving by memoized exhaustive search.
+//!
+//! Every move advances a piece, so the game graph is acyclic and the value of a position follows
+//! from the values of its children alone. Each position reachable from the root is solved once
+//! and remembered, together with its mirror image. Memory grows with the number of reachable
+//! positions, which makes this practical for boards of up to about 21 squares in a few GiB: 3x7
+//! takes some 28 million entries. On 24 squares, 4x6 and 3x8, [df-pn](super::dfpn) still proves
+//! the winner, but not how fast.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{solver::{self, Outcome, Player}, State};
+//! let outcome = solver::solve(State::<3, 4>::default());
+//! assert_eq!(outcome, Outcome { winner: Player::Opponent, plies: 12 });
+//! ```
+
+use super::Outcome;
+use crate::{hash::BuildStateHasher, Bitboard, State};
+use std::collections::HashMap;
+
+/// A memoizing solver, reusable across positions of the same board.
+#[derive(Debug, Clone, Default)]
+pub struct Solver<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    table: HashMap<State<WIDTH, HEIGHT, B>, Outcome, BuildStateHasher>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Solver<WIDTH, HEIGHT, B> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of positions solved so far, counting a position and its mirror image once.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Solves `state`, with the winner playing the fastest win and the loser the slowest loss.
+    pub fn solve(&mut self, state: State<WIDTH, HEIGHT, B>) -> Outcome {
+        if state.is_lost() {
+            return Outcome::loss(0);
+        }
+        let key = state.canonical();
+        if let Some(&outcome) = self.table.get(&key) {
+            return outcome;
+        }
+
+        let outcome = if state.immediate_win_moves().next().is_some() {
+            Outcome::win(1)
+        } else {
+            state
+                .children()
+                .map(|child| self.solve(child).parent())
+                .max()
+                .unwrap_or(Outcome::loss(0))
+        };
+
+        self.table.insert(key, outcome);
+        outcome
+    }
+}
+
+/// Solves `state` from scratch. See [`Solver::solve`].
+pub fn solve<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+    state: State<WIDTH, HEIGHT, B>,
+) -> Outcome {
+    Solver::new().solve(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{verify, Player};
+
+    fn solve_default<const WIDTH: u32, const HEIGHT: u32>() -> Outcome {
+        solve(State::<WIDTH, HEIGHT>::default())
+    }
+
+    #[test]
+    fn small_boards() {
+        assert_eq!(solve_default::<1, 4>(), Outcome::loss(0));
+        assert_eq!(solve_default::<2, 4>(), Outcome::loss(8));
+        assert_eq!(solve_default::<3, 4>(), Outcome::loss(12));
+        assert_eq!(solve_default::<4, 4>(), Outcome::loss(14));
+        assert_eq!(solve_default::<2, 5>(), Outcome::loss(10));
+        assert_eq!(solve_default::<3, 5>(), Outcome::loss(18));
+        assert_eq!(solve_default::<2, 6>(), Outcome::win(15));
+        assert_eq!(solve_default::<2, 7>(), Outcome::loss(20));
+        assert_eq!(solve_default::<2, 8>(), Outcome::win(25));
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn medium_boards() {
+        assert_eq!(solve_default::<5, 4>(), Outcome::loss(16));
+        assert_eq!(solve_default::<6, 4>(), Outcome::loss(18));
+        assert_eq!(solve_default::<4, 5>(), Outcome::loss(22));
+        assert_eq!(solve_default::<3, 6>(), Outcome::win(23));
+        assert_eq!(solve_default::<3, 7>(), Outcome::loss(32));
+    }
+
+    #[test]
+    fn mirror_shares_entries() {
+        let mut solver = Solver::new();
+        for child in State::<3, 5>::default().children() {
+            let outcome = solver.solve(child);
+            let len = solver.len();
+            assert_eq!(solver.solve(child.mirror()), outcome);
+            assert_eq!(solver.len(), len);
+        }
+    }
+
+    #[test]
+    fn matches_search() {
+        let mut solver = Solver::<3, 4>::new();
+        for state in verify::legal_states::<3, 4>() {
+            let outcome = solver.solve(state);
+            if outcome.plies > 4 {
+                continue;
+            }
+            match outcome.winner {
+                Player::Mover => {
+                    assert!(verify::wins_within(state, outcome.plies), "{state:?}");
+                    assert!(!verify::wins_within(state, outcome.plies - 1), "{state:?}");
+                }
+                Player::Opponent => {
+                    assert!(verify::loses_within(state, outcome.plies), "{state:?}");
+                    assert!(
+                        outcome.plies == 0 || !verify::loses_within(state, outcome.plies - 1),
+                        "{state:?}"
+                    );
+                }
+            }
+        }
+    }
+}
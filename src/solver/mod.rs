@@ -0,0 +1,70 @@
+//! Solvers for Breakthrough positions.
+
+use std::cmp::Ordering;
+
+pub mod anf;
+pub mod dfpn;
+pub mod exact;
+pub mod pn;
+pub mod tablebase;
+pub mod verify;
+
+pub use exact::solve;
+
+/// A player, relative to the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    Mover,
+    Opponent,
+}
+
+/// The game-theoretic value of a position: who wins, and in how many plies under optimal play.
+///
+/// Outcomes are ordered by preference of the side to move: faster wins are better, and slower
+/// losses are better than faster ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Outcome {
+    pub winner: Player,
+    pub plies: u32,
+}
+
+impl Outcome {
+    pub fn win(plies: u32) -> Self {
+        Self {
+            winner: Player::Mover,
+            plies,
+        }
+    }
+
+    pub fn loss(plies: u32) -> Self {
+        Self {
+            winner: Player::Opponent,
+            plies,
+        }
+    }
+
+    /// The outcome of a position whose child has this outcome, when moving to it.
+    pub fn parent(self) -> Self {
+        match self.winner {
+            Player::Mover => Self::loss(self.plies + 1),
+            Player::Opponent => Self::win(self.plies + 1),
+        }
+    }
+}
+
+impl Ord for Outcome {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.winner, other.winner) {
+            (Player::Mover, Player::Mover) => other.plies.cmp(&self.plies),
+            (Player::Mover, Player::Opponent) => Ordering::Greater,
+            (Player::Opponent, Player::Mover) => Ordering::Less,
+            (Player::Opponent, Player::Opponent) => self.plies.cmp(&other.plies),
+        }
+    }
+}
+
+impl PartialOrd for Outcome {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,301 @@
+//! Proof-number search, for proving who wins without finding the fastest win.
+//!
+//! The search grows a tree from the root, always expanding a most-proving leaf: one whose solution
+//! would most cheaply contribute to proving or disproving the root. Each node has a proof number,
+//! the number of leaves that must be proven won for the side to move there to win, and a
+//! disproof number, the number that must be proven lost for it to lose. Both are from the side to
+//! move's perspective, so a node's proof number is the smallest disproof number of its children,
+//! and its disproof number is the sum of their proof numbers.
+//!
+//! Plain PN keeps the whole tree in memory. PN² instead evaluates each leaf it expands with a
+//! nested PN search, bounded by the size of the outer tree, and keeps only the leaf's children,
+//! trading time for memory.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{solver::{pn::{Solver, Variant}, Player}, State};
+//! let solver = Solver::new(Variant::Pn2, 16);
+//! let proof = solver.solve(State::<4, 4>::default()).unwrap();
+//! assert_eq!(proof.winner, Player::Opponent);
+//! ```
+
+use super::Player;
+use crate::{Bitboard, State};
+
+const INFINITY: u32 = u32::MAX;
+const NONE: u32 = u32::MAX;
+
+/// Which proof-number search to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variant {
+    Pn,
+    Pn2,
+}
+
+/// A solved position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Proof {
+    pub winner: Player,
+    /// The number of nodes in the (outer) search tree when the root was solved.
+    pub tree_size: usize,
+    /// The number of nodes in the proof or disproof tree contained in the search tree: one
+    /// winning child of each won node, and every child of each lost node.
+    pub proof_size: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node<const WIDTH: u32, const HEIGHT: u32, B> {
+    state: State<WIDTH, HEIGHT, B>,
+    parent: u32,
+    first_child: u32,
+    children: u32,
+    proof: u32,
+    disproof: u32,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Node<WIDTH, HEIGHT, B> {
+    fn leaf(state: State<WIDTH, HEIGHT, B>, parent: u32) -> Self {
+        let (proof, disproof) = if state.is_lost() || state.moves().next().is_none() {
+            (INFINITY, 0)
+        } else if state.immediate_win_moves().next().is_some() {
+            (0, INFINITY)
+        } else {
+            (1, 1)
+        };
+        Self {
+            state,
+            parent,
+            first_child: NONE,
+            children: 0,
+            proof,
+            disproof,
+        }
+    }
+
+    fn is_solved(&self) -> bool {
+        self.proof == 0 || self.disproof == 0
+    }
+}
+
+/// A proof-number search tree.
+struct Tree<const WIDTH: u32, const HEIGHT: u32, B> {
+    nodes: Vec<Node<WIDTH, HEIGHT, B>>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Tree<WIDTH, HEIGHT, B> {
+    fn new(state: State<WIDTH, HEIGHT, B>) -> Self {
+        Self {
+            nodes: vec![Node::leaf(state, NONE)],
+        }
+    }
+
+    fn root(&self) -> &Node<WIDTH, HEIGHT, B> {
+        &self.nodes[0]
+    }
+
+    fn children(&self, i: usize) -> std::ops::Range<usize> {
+        let node = &self.nodes[i];
+        node.first_child as usize..(node.first_child + node.children) as usize
+    }
+
+    /// Grows the tree until the root is solved or the tree has `max_nodes` nodes, expanding
+    /// leaves with `expand`, which returns the children of a state with their proof and disproof
+    /// numbers.
+    fn run(
+        &mut self,
+        max_nodes: usize,
+        mut expand: impl FnMut(State<WIDTH, HEIGHT, B>, usize) -> Vec<Node<WIDTH, HEIGHT, B>>,
+    ) {
+        while !self.root().is_solved() && self.nodes.len() < max_nodes {
+            let leaf = self.most_proving();
+            let children = expand(self.nodes[leaf].state, self.nodes.len());
+            self.nodes[leaf].first_child = self.nodes.len() as u32;
+            self.nodes[leaf].children = children.len() as u32;
+            for mut child in children {
+                child.parent = leaf as u32;
+                self.nodes.push(child);
+            }
+            self.update(leaf);
+        }
+    }
+
+    fn most_proving(&self) -> usize {
+        let mut i = 0;
+        while self.nodes[i].children > 0 {
+            let proof = self.nodes[i].proof;
+            i = self
+                .children(i)
+                .find(|&child| self.nodes[child].disproof == proof)
+                .expect("no child determines the proof number");
+        }
+        i
+    }
+
+    /// Recomputes the numbers of `i` and its ancestors from their children.
+    fn update(&mut self, mut i: usize) {
+        loop {
+            let children = self.children(i);
+            let proof = children
+                .clone()
+                .map(|child| self.nodes[child].disproof)
+                .min()
+                .unwrap_or(INFINITY);
+            let disproof = children
+                .map(|child| self.nodes[child].proof)
+                .fold(0, u32::saturating_add);
+            let node = &mut self.nodes[i];
+            node.proof = proof;
+            node.disproof = disproof;
+            if node.parent == NONE {
+                break;
+            }
+            i = node.parent as usize;
+        }
+    }
+
+    fn proof_size(&self, i: usize) -> usize {
+        let node = &self.nodes[i];
+        let mut children = self.children(i);
+        if node.proof == 0 {
+            1 + children
+                .find(|&child| self.nodes[child].disproof == 0)
+                .map_or(0, |child| self.proof_size(child))
+        } else {
+            1 + children.map(|child| self.proof_size(child)).sum::<usize>()
+        }
+    }
+}
+
+/// Expands a state fully, for plain PN and the inner search of PN².
+fn expand<const WIDTH: u32, const HEIGHT: u32, B: Bitboard>(
+    state: State<WIDTH, HEIGHT, B>,
+) -> Vec<Node<WIDTH, HEIGHT, B>> {
+    state
+        .children()
+        .map(|child| Node::leaf(child, NONE))
+        .collect()
+}
+
+/// A proof-number solver with a memory limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solver<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    variant: Variant,
+    max_nodes: usize,
+    bitboard: std::marker::PhantomData<B>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Solver<WIDTH, HEIGHT, B> {
+    /// Creates a solver using about `megabytes` MiB for its trees.
+    pub fn new(variant: Variant, megabytes: usize) -> Self {
+        Self {
+            variant,
+            max_nodes: (megabytes << 20) / std::mem::size_of::<Node<WIDTH, HEIGHT, B>>(),
+            bitboard: std::marker::PhantomData,
+        }
+    }
+
+    /// Solves `state`, or returns `None` if it runs out of memory first.
+    pub fn solve(&self, state: State<WIDTH, HEIGHT, B>) -> Option<Proof> {
+        let mut tree = Tree::new(state);
+        match self.variant {
+            Variant::Pn => tree.run(self.max_nodes, |state, _| expand(state)),
+            Variant::Pn2 => tree.run(self.max_nodes, |state, size| {
+                // The inner tree shares the memory budget with the outer one.
+                let budget = size.min(self.max_nodes.saturating_sub(size)).max(1);
+                let mut inner = Tree::new(state);
+                inner.run(budget, |state, _| expand(state));
+                if inner.nodes.len() == 1 {
+                    inner.run(2, |state, _| expand(state));
+                }
+                inner
+                    .children(0)
+                    .map(|child| Node {
+                        first_child: NONE,
+                        children: 0,
+                        ..inner.nodes[child]
+                    })
+                    .collect()
+            }),
+        }
+
+        let root = tree.root();
+        let winner = if root.proof == 0 {
+            Player::Mover
+        } else if root.disproof == 0 {
+            Player::Opponent
+        } else {
+            return None;
+        };
+        Some(Proof {
+            winner,
+            tree_size: tree.nodes.len(),
+            proof_size: tree.proof_size(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{exact, verify};
+
+    fn check<const WIDTH: u32, const HEIGHT: u32>(
+        state: State<WIDTH, HEIGHT>,
+        variants: &[Variant],
+    ) {
+        let expected = exact::solve(state).winner;
+        for &variant in variants {
+            let proof = Solver::new(variant, 64).solve(state).unwrap();
+            assert_eq!(proof.winner, expected, "{variant:?} {state:?}");
+            assert!(proof.proof_size <= proof.tree_size);
+        }
+    }
+
+    const BOTH: &[Variant] = &[Variant::Pn, Variant::Pn2];
+
+    #[test]
+    fn matches_exact_solver() {
+        check(State::<3, 4>::default(), BOTH);
+        check(State::<4, 4>::default(), BOTH);
+        check(State::<2, 5>::default(), BOTH);
+        // Plain PN needs about 1.8 million nodes here.
+        check(State::<3, 5>::default(), &[Variant::Pn2]);
+        for state in verify::legal_states::<3, 4>().step_by(997) {
+            check(state, BOTH);
+        }
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn medium_boards() {
+        check(State::<5, 4>::default(), &[Variant::Pn2]);
+        check(State::<4, 5>::default(), &[Variant::Pn2]);
+    }
+
+    #[test]
+    fn proof_size() {
+        // Won immediately: the root is a solved leaf.
+        let state: State<3, 4> = "3/3/.W./B.B w".parse().unwrap();
+        let proof = Solver::new(Variant::Pn, 1).solve(state).unwrap();
+        assert_eq!(proof.winner, Player::Mover);
+        assert_eq!((proof.tree_size, proof.proof_size), (1, 1));
+
+        let state = State::<3, 4>::default();
+        let pn = Solver::new(Variant::Pn, 64).solve(state).unwrap();
+        let pn2 = Solver::new(Variant::Pn2, 64).solve(state).unwrap();
+        assert!(pn2.tree_size < pn.tree_size);
+    }
+
+    #[test]
+    fn memory_limit() {
+        let state = State::<4, 5>::default();
+        assert_eq!(Solver::new(Variant::Pn, 0).solve(state), None);
+        let tiny = Solver {
+            variant: Variant::Pn2,
+            max_nodes: 50,
+            bitboard: std::marker::PhantomData,
+        };
+        assert_eq!(tiny.solve(state), None);
+    }
+}
@@ -0,0 +1,398 @@
+//! Endgame tablebases by retrograde analysis.
+//!
+//! A tablebase holds the [`Outcome`] of every legal position with at most a given number of
+//! pieces, one byte per position, numbered by [`index::rank`] within each material class.
+//!
+//! Captures lead to classes with fewer pieces, so classes are generated in order of increasing
+//! piece count, and quiet moves only connect a class with its reversal, like 3 against 2 with 2
+//! against 3. Each such pair is solved together by retrograde analysis: starting from the
+//! positions that are decided immediately, outcomes are settled in order of increasing length and
+//! propagated to parents found by unmaking quiet moves. A parent is won as soon as a child is
+//! lost, and lost once all of its children are won.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::{solver::{self, tablebase::Tablebase}, State};
+//! let tablebase = Tablebase::<3, 4>::generate(4);
+//! let state: State<3, 4> = "W2/1W1/1B1/B2 w".parse().unwrap();
+//! assert_eq!(tablebase.probe(state), Some(solver::solve(state)));
+//! assert_eq!(tablebase.probe(State::default()), None);
+//! ```
+
+use super::{Outcome, Player};
+use crate::{
+    index::{self, Material},
+    Bitboard, State,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+const MAGIC: &[u8; 4] = b"BTTB";
+const VERSION: u8 = 1;
+
+/// Marks an unsolved position during generation.
+const UNKNOWN: u8 = u8::MAX;
+
+/// Stores an outcome as its length: wins take an odd number of plies and losses an even number.
+fn encode(outcome: Outcome) -> u8 {
+    debug_assert_eq!(outcome.plies % 2 == 1, outcome.winner == Player::Mover);
+    assert!(outcome.plies < UNKNOWN as u32, "game too long");
+    outcome.plies as u8
+}
+
+fn decode(code: u8) -> Outcome {
+    if code % 2 == 1 {
+        Outcome::win(code as u32)
+    } else {
+        Outcome::loss(code as u32)
+    }
+}
+
+/// The outcomes of all positions with at most a given number of pieces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tablebase<const WIDTH: u32, const HEIGHT: u32, B = u64> {
+    pieces: u32,
+    /// The encoded outcomes of each material class, in the order of [`index::materials`], empty
+    /// for classes with too many pieces.
+    tables: Vec<Vec<u8>>,
+    bitboard: std::marker::PhantomData<B>,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32, B: Bitboard> Tablebase<WIDTH, HEIGHT, B> {
+    /// Where `material` is in [`index::materials`].
+    fn class(material: Material) -> usize {
+        (material.mine * (2 * WIDTH + 1) + material.theirs) as usize
+    }
+
+    fn covers(&self, material: Material) -> bool {
+        material.mine + material.theirs <= self.pieces
+            && material.mine <= 2 * WIDTH
+            && material.theirs <= 2 * WIDTH
+    }
+
+    fn empty(pieces: u32) -> Self {
+        Self {
+            // No position has more, and a larger count couldn't be read back.
+            pieces: pieces.min(4 * WIDTH),
+            tables: vec![Vec::new(); index::materials::<WIDTH, HEIGHT>().count()],
+            bitboard: std::marker::PhantomData,
+        }
+    }
+
+    /// Generates the tablebase of all positions with at most `pieces` pieces in total, capped at
+    /// the `4 * WIDTH` pieces of a full board.
+    pub fn generate(pieces: u32) -> Self {
+        let mut tablebase = Self::empty(pieces);
+        for total in 0..=tablebase.pieces {
+            for mine in 0..=total / 2 {
+                let material = Material {
+                    mine,
+                    theirs: total - mine,
+                };
+                if tablebase.covers(material) {
+                    tablebase.solve_pair(material);
+                }
+            }
+        }
+        tablebase
+    }
+
+    /// The number of pieces the tablebase goes up to.
+    pub fn pieces(&self) -> u32 {
+        self.pieces
+    }
+
+    /// The number of positions in the tablebase.
+    pub fn len(&self) -> usize {
+        self.tables.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The outcome of `state`, if it has few enough pieces and is legal.
+    pub fn probe(&self, state: State<WIDTH, HEIGHT, B>) -> Option<Outcome> {
+        let material = state.material();
+        let last_row = B::from_u64(State::<WIDTH, HEIGHT, B>::ROW_MASK)
+            << State::<WIDTH, HEIGHT, B>::AREA - WIDTH;
+        if !self.covers(material) || state.me & last_row != B::ZERO {
+            return None;
+        }
+        let code = self.tables[Self::class(material)][index::rank(&state) as usize];
+        Some(decode(code))
+    }
+
+    /// Solves the class of `material` together with its reversal, given all classes with fewer
+    /// pieces.
+    fn solve_pair(&mut self, material: Material) {
+        let reversed = Material {
+            mine: material.theirs,
+            theirs: material.mine,
+        };
+        let classes = if material == reversed {
+            vec![material]
+        } else {
+            vec![material, reversed]
+        };
+        let sizes: Vec<_> = classes
+            .iter()
+            .map(|&material| index::positions::<WIDTH, HEIGHT>(material) as usize)
+            .collect();
+        let len = sizes.iter().sum();
+        let offset = |material: Material| if material == classes[0] { 0 } else { sizes[0] };
+        let state = |i: usize| {
+            let (material, rank) = if i < sizes[0] {
+                (classes[0], i)
+            } else {
+                (classes[1], i - sizes[0])
+            };
+            index::unrank::<WIDTH, HEIGHT, B>(material, rank as u64)
+        };
+
+        let mut values = vec![UNKNOWN; len];
+        // The best outcome over the children settled so far, and how many quiet ones aren't.
+        let mut best = vec![UNKNOWN; len];
+        let mut pending = vec![0u8; len];
+        // Candidate outcomes by length, settled shortest first.
+        let mut queue: Vec<Vec<(usize, u8)>> = Vec::new();
+        let push = |queue: &mut Vec<Vec<(usize, u8)>>, i: usize, code: u8| {
+            let plies = code as usize;
+            if queue.len() <= plies {
+                queue.resize_with(plies + 1, Vec::new);
+            }
+            queue[plies].push((i, code));
+        };
+
+        for i in 0..len {
+            let state = state(i);
+            if state.is_lost() {
+                push(&mut queue, i, encode(Outcome::loss(0)));
+                continue;
+            }
+
+            let mut best_capture = None;
+            let mut moves = 0;
+            for mv in state.moves() {
+                moves += 1;
+                if mv.capture {
+                    let outcome = self
+                        .probe(state.apply(mv))
+                        .expect("smaller class not solved");
+                    best_capture = best_capture.max(Some(outcome.parent()));
+                } else {
+                    pending[i] += 1;
+                }
+            }
+
+            match best_capture {
+                _ if moves == 0 => push(&mut queue, i, encode(Outcome::loss(0))),
+                None => {}
+                Some(outcome) => {
+                    best[i] = encode(outcome);
+                    // A win can be settled before the quiet children, if none of them is faster.
+                    if pending[i] == 0 || outcome.winner == Player::Mover {
+                        push(&mut queue, i, best[i]);
+                    }
+                }
+            }
+        }
+
+        let mut plies = 0;
+        while plies < queue.len() {
+            for (i, code) in std::mem::take(&mut queue[plies]) {
+                if values[i] != UNKNOWN {
+                    continue;
+                }
+                values[i] = code;
+
+                // Parents by captures have more pieces, and are settled later.
+                let child = state(i);
+                let quiet = |parent: &State<WIDTH, HEIGHT, B>| {
+                    parent.them.count_ones() == child.me.count_ones()
+                };
+                let outcome = decode(code).parent();
+                for parent in child.parents().filter(quiet) {
+                    let j = offset(parent.material()) + index::rank(&parent) as usize;
+                    if values[j] != UNKNOWN {
+                        continue;
+                    }
+                    if best[j] == UNKNOWN || decode(best[j]) < outcome {
+                        best[j] = encode(outcome);
+                    }
+                    pending[j] -= 1;
+                    if outcome.winner == Player::Mover || pending[j] == 0 {
+                        push(&mut queue, j, best[j]);
+                    }
+                }
+            }
+            plies += 1;
+        }
+        debug_assert!(!values.contains(&UNKNOWN));
+
+        let (first, second) = values.split_at(sizes[0]);
+        self.tables[Self::class(classes[0])] = first.to_vec();
+        if let Some(&reversed) = classes.get(1) {
+            self.tables[Self::class(reversed)] = second.to_vec();
+        }
+    }
+
+    /// Writes the tablebase in a compact binary format: a header, then one byte per position.
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        for field in [WIDTH, HEIGHT, self.pieces] {
+            writer.write_all(&field.to_le_bytes())?;
+        }
+        for table in &self.tables {
+            writer.write_all(table)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tablebase written by [`write`](Self::write) for the same board.
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if header[..4] != *MAGIC || header[4] != VERSION {
+            return Err(invalid("not a tablebase"));
+        }
+        let mut fields = [0; 3];
+        for field in &mut fields {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            *field = u32::from_le_bytes(bytes);
+        }
+        let [width, height, pieces] = fields;
+        if (width, height) != (WIDTH, HEIGHT) {
+            return Err(invalid("tablebase of another board size"));
+        }
+        if pieces > 4 * WIDTH {
+            return Err(invalid("too many pieces in tablebase"));
+        }
+
+        // Check every class size before reading any of them, and read them without allocating
+        // ahead of the data, so that a corrupt header can't cause a huge allocation.
+        let mut tablebase = Self::empty(pieces);
+        let mut sizes = Vec::new();
+        for material in index::materials::<WIDTH, HEIGHT>() {
+            if tablebase.covers(material) {
+                let size = index::checked_positions::<WIDTH, HEIGHT>(material)
+                    .and_then(|size| usize::try_from(size).ok())
+                    .ok_or_else(|| invalid("tablebase too large"))?;
+                sizes.push((material, size));
+            }
+        }
+        for (material, size) in sizes {
+            let mut table = Vec::new();
+            (&mut reader).take(size as u64).read_to_end(&mut table)?;
+            if table.len() != size {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if table.contains(&UNKNOWN) {
+                return Err(invalid("unsolved position in tablebase"));
+            }
+            tablebase.tables[Self::class(material)] = table;
+        }
+        if reader.read(&mut [0])? != 0 {
+            return Err(invalid("trailing data after tablebase"));
+        }
+        Ok(tablebase)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{exact::Solver, verify};
+
+    #[test]
+    fn matches_exact_solver() {
+        let tablebase = Tablebase::<3, 4>::generate(12);
+        let mut solver = Solver::new();
+        for state in verify::legal_states::<3, 4>() {
+            assert_eq!(
+                tablebase.probe(state),
+                Some(solver.solve(state)),
+                "{state:?}"
+            );
+        }
+        assert_eq!(tablebase.len(), verify::legal_states::<3, 4>().count());
+    }
+
+    #[test]
+    fn few_pieces() {
+        let tablebase = Tablebase::<4, 5, u32>::generate(4);
+        let mut solver = Solver::new();
+        for material in index::materials::<4, 5>().filter(|m| m.mine + m.theirs <= 4) {
+            let positions = index::positions::<4, 5>(material);
+            for rank in (0..positions).step_by(97) {
+                let state = index::unrank::<4, 5, u32>(material, rank);
+                assert_eq!(
+                    tablebase.probe(state),
+                    Some(solver.solve(state)),
+                    "{state:?}"
+                );
+            }
+        }
+        assert_eq!(tablebase.probe(State::default()), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let tablebase = Tablebase::<4, 4>::generate(3);
+        let mut bytes = Vec::new();
+        tablebase.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 17 + tablebase.len());
+        assert_eq!(Tablebase::read(bytes.as_slice()).unwrap(), tablebase);
+
+        let error = Tablebase::<4, 5>::read(bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        bytes.push(0);
+        assert!(Tablebase::<4, 4>::read(bytes.as_slice()).is_err());
+        assert!(Tablebase::<4, 4>::read(&bytes[..20]).is_err());
+
+        // More pieces than fit on the board are capped, so that the header stays readable.
+        let tablebase = Tablebase::<2, 4>::generate(9);
+        assert_eq!(tablebase.pieces(), 8);
+        let mut bytes = Vec::new();
+        tablebase.write(&mut bytes).unwrap();
+        assert_eq!(Tablebase::read(bytes.as_slice()).unwrap(), tablebase);
+    }
+
+    #[test]
+    fn corrupt_header() {
+        let header = |pieces: u32| {
+            let mut bytes = MAGIC.to_vec();
+            bytes.push(VERSION);
+            for field in [8, 8, pieces] {
+                bytes.extend(field.to_le_bytes());
+            }
+            bytes
+        };
+        let error = Tablebase::<8, 8>::read(header(33).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // Full material on 8x8 has more positions than fit in a `u64`.
+        let error = Tablebase::<8, 8>::read(header(32).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // Classes that fit, but aren't there.
+        let error = Tablebase::<8, 8>::read(header(12).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,222 @@
+//! Cross-validation of ANF formulas against enumerative search.
+//!
+//! [`cross_check`] is exhaustive over all `3^AREA` colourings of the board, which is quick for
+//! 3x4, takes hours for 4x4, and is out of reach beyond that. Larger boards can be checked on a
+//! sample of positions with [`cross_check_states`], though building the formulas alone takes
+//! minutes from 4x4 on.
+//!
+//! # Examples
+//!
+//! ```
+//! use breakthrough_anf::solver::verify;
+//! verify::cross_check::<3, 4>(1).unwrap();
+//! ```
+
+use super::anf::{self, Formula};
+use crate::State;
+use std::fmt;
+
+/// Returns every legal position of the board: no square holds pieces of both sides, neither side
+/// has more pieces than it starts with, and the side to move has no piece on the last row.
+///
+/// Panics on boards of more than 40 squares, whose `3^AREA` colourings don't fit in a `u64`.
+pub fn legal_states<const WIDTH: u32, const HEIGHT: u32>(
+) -> impl Iterator<Item = State<WIDTH, HEIGHT>> {
+    let area = State::<WIDTH, HEIGHT>::AREA;
+    assert!(area <= 40, "too many colourings to enumerate");
+    let last_row = State::<WIDTH, HEIGHT>::ROW_MASK << area - WIDTH;
+    (0..3u64.pow(area)).filter_map(move |mut code| {
+        let mut state = State { me: 0, them: 0 };
+        for i in 0..area {
+            match code % 3 {
+                1 => state.me |= 1 << i,
+                2 => state.them |= 1 << i,
+                _ => {}
+            }
+            code /= 3;
+        }
+        (state.me & last_row == 0
+            && state.me.count_ones() <= 2 * WIDTH
+            && state.them.count_ones() <= 2 * WIDTH)
+            .then_some(state)
+    })
+}
+
+/// Whether the side to move wins within `k` plies, by exhaustive search.
+pub fn wins_within<const WIDTH: u32, const HEIGHT: u32>(
+    state: State<WIDTH, HEIGHT>,
+    k: u32,
+) -> bool {
+    k > 0 && !state.is_lost() && state.children().any(|child| loses_within(child, k - 1))
+}
+
+/// Whether the side to move loses within `k` plies, by exhaustive search. A side without legal
+/// moves has lost.
+pub fn loses_within<const WIDTH: u32, const HEIGHT: u32>(
+    state: State<WIDTH, HEIGHT>,
+    k: u32,
+) -> bool {
+    state.is_lost()
+        || state
+            .children()
+            .all(|child| k > 0 && wins_within(child, k - 1))
+}
+
+/// A position on which a formula and the search disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counterexample<const WIDTH: u32, const HEIGHT: u32> {
+    pub state: State<WIDTH, HEIGHT>,
+    pub plies: u32,
+    /// The answer of the search.
+    pub expected: bool,
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32> fmt::Display for Counterexample<WIDTH, HEIGHT> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "win within {} plies: search says {}, formula says {}",
+            self.plies, self.expected, !self.expected,
+        )?;
+        write!(f, "{}", self.state)
+    }
+}
+
+impl<const WIDTH: u32, const HEIGHT: u32> std::error::Error for Counterexample<WIDTH, HEIGHT> {}
+
+/// Checks [`anf::win_within`] against [`wins_within`] on every legal position, returning the
+/// first position on which they disagree.
+pub fn cross_check<const WIDTH: u32, const HEIGHT: u32>(
+    k: u32,
+) -> Result<(), Counterexample<WIDTH, HEIGHT>> {
+    cross_check_states(k, legal_states())
+}
+
+/// Checks [`anf::win_within`] against [`wins_within`] on `states`, which must be legal, returning
+/// the first position on which they disagree.
+pub fn cross_check_states<const WIDTH: u32, const HEIGHT: u32>(
+    k: u32,
+    states: impl IntoIterator<Item = State<WIDTH, HEIGHT>>,
+) -> Result<(), Counterexample<WIDTH, HEIGHT>> {
+    let formula = Formula::from_anf(&anf::win_within::<WIDTH, HEIGHT>(k));
+    states.into_iter().try_for_each(|state| {
+        let expected = wins_within(state, k);
+        if formula.evaluate(state) == expected {
+            Ok(())
+        } else {
+            Err(Counterexample {
+                state,
+                plies: k,
+                expected,
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<const WIDTH: u32, const HEIGHT: u32>(k: u32) {
+        if let Err(counterexample) = cross_check::<WIDTH, HEIGHT>(k) {
+            panic!("{counterexample}");
+        }
+    }
+
+    /// The positions along `games` pseudo-random games from the default position.
+    fn game_states<const WIDTH: u32, const HEIGHT: u32>(games: u64) -> Vec<State<WIDTH, HEIGHT>> {
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let mut states = Vec::new();
+        for _ in 0..games {
+            let mut state = State::default();
+            loop {
+                states.push(state);
+                let children: Vec<_> = state.children().collect();
+                if state.is_lost() || children.is_empty() {
+                    break;
+                }
+                seed = seed
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                state = children[(seed >> 33) as usize % children.len()];
+            }
+        }
+        states
+    }
+
+    fn check_sample<const WIDTH: u32, const HEIGHT: u32>(
+        k: u32,
+        states: impl IntoIterator<Item = State<WIDTH, HEIGHT>>,
+    ) {
+        if let Err(counterexample) = cross_check_states(k, states) {
+            panic!("{counterexample}");
+        }
+    }
+
+    #[test]
+    fn board_3x4() {
+        for k in 0..3 {
+            check::<3, 4>(k);
+        }
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn board_3x4_deep() {
+        check::<3, 4>(3);
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn board_4x4() {
+        for k in 0..2 {
+            check::<4, 4>(k);
+        }
+    }
+
+    // Building the formulas dominates on the larger boards: 4x4 at k = 1 takes minutes even in
+    // release builds, and 5x4 longer still.
+    #[test]
+    #[ignore = "slow"]
+    fn board_4x4_sampled() {
+        for k in 0..2 {
+            check_sample::<4, 4>(k, legal_states().step_by(10_007));
+        }
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn board_5x4() {
+        for k in 0..2 {
+            check::<5, 4>(k);
+        }
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn board_5x4_sampled() {
+        for k in 0..2 {
+            check_sample::<5, 4>(k, game_states(50));
+        }
+    }
+
+    #[test]
+    fn too_many_colourings() {
+        assert!(std::panic::catch_unwind(|| legal_states::<8, 5>().next()).is_ok());
+        assert!(std::panic::catch_unwind(|| legal_states::<7, 6>().next()).is_err());
+    }
+
+    #[test]
+    fn readable_counterexample() {
+        let counterexample = Counterexample {
+            state: State::<3, 4>::default(),
+            plies: 1,
+            expected: false,
+        };
+        assert_eq!(
+            counterexample.to_string(),
+            "win within 1 plies: search says false, formula says true\n\
+             4 BBB\n3 BBB\n2 WWW\n1 WWW\n  abc\n",
+        );
+    }
+}